@@ -0,0 +1,7 @@
+use std::env;
+
+// the C test compiles with the cc crate, which needs the target
+fn main() {
+    println!("cargo:rustc-env=TARGET={}", env::var("TARGET").unwrap());
+    println!("cargo:rustc-env=HOST={}", env::var("HOST").unwrap());
+}
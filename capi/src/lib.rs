@@ -0,0 +1,3 @@
+// Builds librosrust_tf_c as a static and a shared library for C. It has
+// a crate of its own so that the Rust library is not built three times.
+pub use rosrust_tf::ffi::*;
@@ -0,0 +1,78 @@
+#include "rosrust_tf.h"
+
+#include <math.h>
+#include <stdio.h>
+#include <string.h>
+
+static int failures = 0;
+
+#define CHECK(condition) do { \
+    if (!(condition)) { \
+        fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+        failures++; \
+    } \
+} while (0)
+
+static TfTransform make_transform(double x, double yaw, int64_t stamp_nanos) {
+    TfTransform transform = {0};
+    transform.translation_x = x;
+    transform.rotation_z = sin(yaw / 2.0);
+    transform.rotation_w = cos(yaw / 2.0);
+    transform.stamp_nanos = stamp_nanos;
+    return transform;
+}
+
+static void test_lookup(void) {
+    TfBuffer *buffer = tf_buffer_new();
+    TfTransform map_odom = make_transform(1.0, 0.0, 0);
+    TfTransform result;
+
+    CHECK(tf_buffer_set_transform(buffer, "map", "odom", &map_odom, 1) == TF_OK);
+    for (int i = 1; i <= 2; i++) {
+        TfTransform odom_base = make_transform(i, M_PI / 2.0, i * 1000000000LL);
+        CHECK(tf_buffer_set_transform(buffer, "odom", "base_link", &odom_base, 0) == TF_OK);
+    }
+
+    CHECK(tf_buffer_lookup_transform(buffer, "map", "base_link", 1500000000LL, &result) == TF_OK);
+    CHECK(fabs(result.translation_x - 2.5) < 1e-9);
+    CHECK(fabs(result.rotation_z - sin(M_PI / 4.0)) < 1e-9);
+    CHECK(result.stamp_nanos == 1500000000LL);
+
+    CHECK(tf_buffer_can_transform(buffer, "base_link", "map", 0) == 1);
+    CHECK(tf_buffer_can_transform(buffer, "map", "base_link", 5000000000LL) == 0);
+    tf_buffer_free(buffer);
+}
+
+static void test_errors(void) {
+    TfBuffer *buffer = tf_buffer_new();
+    TfTransform transform = make_transform(1.0, 0.0, 1000000000LL);
+    TfTransform result;
+
+    CHECK(tf_buffer_set_transform(buffer, "map", "odom", &transform, 0) == TF_OK);
+    CHECK(tf_buffer_set_transform(buffer, "world", "robot", &transform, 0) == TF_OK);
+    CHECK(tf_buffer_lookup_transform(buffer, "map", "laser", 0, &result) == TF_LOOKUP_ERROR);
+    CHECK(tf_buffer_lookup_transform(buffer, "map", "robot", 0, &result) == TF_CONNECTIVITY_ERROR);
+    CHECK(tf_buffer_lookup_transform(buffer, "map", "odom", 2000000000LL, &result) == TF_EXTRAPOLATION_ERROR);
+    CHECK(tf_buffer_lookup_transform(buffer, NULL, "odom", 0, &result) == TF_INVALID_ARGUMENT_ERROR);
+    CHECK(tf_buffer_lookup_transform(NULL, "map", "odom", 0, &result) == TF_INVALID_ARGUMENT_ERROR);
+    CHECK(tf_buffer_set_transform(buffer, "map", "map", &transform, 0) == TF_INVALID_ARGUMENT_ERROR);
+    transform.rotation_w = 0.0;
+    CHECK(tf_buffer_set_transform(buffer, "map", "base_link", &transform, 0) == TF_INVALID_ARGUMENT_ERROR);
+    transform.rotation_w = NAN;
+    CHECK(tf_buffer_set_transform(buffer, "map", "base_link", &transform, 0) == TF_INVALID_ARGUMENT_ERROR);
+    CHECK(strcmp(tf_error_string(TF_CONNECTIVITY_ERROR), "connectivity error") == 0);
+
+    tf_buffer_free(buffer);
+    tf_buffer_free(NULL);
+}
+
+int main(void) {
+    test_lookup();
+    test_errors();
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed\n");
+    return 0;
+}
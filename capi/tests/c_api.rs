@@ -0,0 +1,42 @@
+// Builds tests/c/test_buffer.c against the static library and runs it.
+// cargo builds the static library next to the rlib the test links with,
+// in target/[<triple>/]<profile>/deps.
+// The compiler comes from the cc crate, set CC and CFLAGS to change it.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+// what the Rust standard library needs, see rustc --print native-static-libs
+#[cfg(target_os = "linux")]
+const NATIVE_LIBS: &[&str] = &["-lpthread", "-ldl", "-lm"];
+#[cfg(not(target_os = "linux"))]
+const NATIVE_LIBS: &[&str] = &["-lpthread", "-lm"];
+
+// the command line below is the one of gcc and clang
+#[cfg(unix)]
+#[test]
+fn c_api() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let out_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+
+    let compiler = cc::Build::new()
+        .cargo_metadata(false)
+        .target(env!("TARGET"))
+        .host(env!("HOST"))
+        .opt_level(0)
+        .get_compiler();
+    let executable = out_dir.join("test_buffer");
+    let status = compiler.to_command()
+        .arg(manifest_dir.join("tests").join("c").join("test_buffer.c"))
+        .arg("-I").arg(manifest_dir.join("..").join("include"))
+        .arg(out_dir.join("librosrust_tf_c.a"))
+        .args(NATIVE_LIBS)
+        .arg("-o").arg(&executable)
+        .status()
+        .unwrap_or_else(|err| panic!("could not run {:?}: {}", compiler.path(), err));
+    assert!(status.success(), "compiling the C test failed");
+
+    let output = Command::new(&executable).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,140 @@
+#ifndef ROSRUST_TF_H
+#define ROSRUST_TF_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Success.
+ */
+#define TF_OK 0
+
+/**
+ * A frame does not exist.
+ */
+#define TF_LOOKUP_ERROR 1
+
+/**
+ * The frames are in trees that are not connected.
+ */
+#define TF_CONNECTIVITY_ERROR 2
+
+/**
+ * A link has no data at the requested time.
+ */
+#define TF_EXTRAPOLATION_ERROR 3
+
+/**
+ * A null pointer, an invalid frame id or an invalid transform.
+ */
+#define TF_INVALID_ARGUMENT_ERROR 4
+
+/**
+ * Any other failure, including panics, which never reach C.
+ */
+#define TF_OTHER_ERROR 5
+
+/**
+ * A transform buffer, opaque to C.
+ *
+ * Every function but tf_buffer_free may be called on the same handle from
+ * several threads at once. Pointers passed to the API must be null or
+ * valid, and frame ids nul-terminated UTF-8. Null pointers are reported as
+ * TF_INVALID_ARGUMENT_ERROR.
+ */
+typedef struct TfBuffer TfBuffer;
+
+/**
+ * A transform and its stamp in nanoseconds. Rotations are quaternions,
+ * they are normalized when set.
+ */
+typedef struct TfTransform {
+  double translation_x;
+  double translation_y;
+  double translation_z;
+  double rotation_x;
+  double rotation_y;
+  double rotation_z;
+  double rotation_w;
+  int64_t stamp_nanos;
+} TfTransform;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an empty buffer, owned by the caller until tf_buffer_free.
+ */
+struct TfBuffer *tf_buffer_new(void);
+
+/**
+ * Frees a buffer, null is ignored.
+ *
+ * # Safety
+ *
+ * `handle` must be null or come from tf_buffer_new, and must not be used
+ * by any thread during or after this call.
+ */
+void tf_buffer_free(struct TfBuffer *handle);
+
+/**
+ * Inserts the transform from `parent_frame` to `child_frame`. The strings
+ * and `transform` are copied, the caller keeps them. Returns
+ * TF_INVALID_ARGUMENT_ERROR for invalid frame ids and for rotations that
+ * are zero or not finite.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live buffer, the frame ids null or
+ * nul-terminated strings and `transform` null or valid.
+ */
+int tf_buffer_set_transform(const struct TfBuffer *handle,
+                            const char *parent_frame,
+                            const char *child_frame,
+                            const struct TfTransform *transform,
+                            int is_static);
+
+/**
+ * Looks up the transform that maps `source_frame` coordinates into
+ * `target_frame`. A time of zero asks for the latest transform. `result`
+ * is only written on success.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live buffer, the frame ids null or
+ * nul-terminated strings and `result` null or valid for writes.
+ */
+int tf_buffer_lookup_transform(const struct TfBuffer *handle,
+                               const char *target_frame,
+                               const char *source_frame,
+                               int64_t stamp_nanos,
+                               struct TfTransform *result);
+
+/**
+ * 1 if tf_buffer_lookup_transform would succeed, 0 otherwise.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live buffer and the frame ids null or
+ * nul-terminated strings.
+ */
+int tf_buffer_can_transform(const struct TfBuffer *handle,
+                            const char *target_frame,
+                            const char *source_frame,
+                            int64_t stamp_nanos);
+
+/**
+ * A description of an error code. The string is static, do not free it.
+ */
+const char *tf_error_string(int code);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ROSRUST_TF_H */
@@ -0,0 +1,320 @@
+use super::transform_storage::Stamp;
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{Buffer, Transform};
+use super::clock::Clock;
+use super::transform_watch::WatchQueue;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll, Waker};
+
+const TIMER_PERIOD: std::time::Duration = std::time::Duration::from_millis(10);
+
+// Wakers of pending lookups and queues of watches, keyed by the frames
+// whose updates could make them answerable. Inserts only reach the ones
+// registered for that frame. Each lookup registers under its own id, so
+// polling again replaces its entries and dropping it removes them.
+pub struct TransformWaiters {
+    by_frame      : Mutex<HashMap<String, Vec<(u64, Waker)>>>,
+    watchers      : Mutex<HashMap<String, Vec<Weak<WatchQueue>>>>,
+    // deadlines in nanoseconds on the buffer's clock
+    timeouts      : Mutex<HashMap<u64, (i64, Waker)>>,
+    timer_running : Mutex<bool>,
+    next_id       : AtomicU64
+}
+
+impl TransformWaiters {
+
+    pub fn new() -> TransformWaiters {
+        TransformWaiters {
+            by_frame      : Mutex::new(HashMap::new()),
+            watchers      : Mutex::new(HashMap::new()),
+            timeouts      : Mutex::new(HashMap::new()),
+            timer_running : Mutex::new(false),
+            next_id       : AtomicU64::new(0)
+        }
+    }
+
+    pub fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn register(&self, id: u64, frames: &[String], waker: &Waker) {
+        let mut by_frame = self.by_frame.lock().unwrap();
+        remove_id(&mut by_frame, id);
+        for frame in frames.iter() {
+            let registered = by_frame.entry(frame.clone()).or_insert_with(Vec::new);
+            if !registered.iter().any(|x| x.0 == id) {
+                registered.push((id, waker.clone()));
+            }
+        }
+    }
+
+    pub fn deregister(&self, id: u64) {
+        remove_id(&mut self.by_frame.lock().unwrap(), id);
+        self.timeouts.lock().unwrap().remove(&id);
+    }
+
+    // `frames` is the chain the watch composes, it grows as new parents show up
+    pub fn add_watcher(&self, frames: &[String], queue: &Arc<WatchQueue>) {
+        add_watcher(&mut self.watchers.lock().unwrap(), frames, queue);
+    }
+
+    pub fn remove_watcher(&self, queue: &Arc<WatchQueue>) {
+        let mut watchers = self.watchers.lock().unwrap();
+        for registered in watchers.values_mut() {
+            registered.retain(|x| x.upgrade().map_or(false, |x| !Arc::ptr_eq(&x, queue)));
+        }
+        watchers.retain(|_, registered| !registered.is_empty());
+    }
+
+    // `parent_chain` gives the new parent of `frame` and its ancestors, only
+    // asked for when a watch follows `frame`
+    pub fn notify<F: FnOnce() -> Vec<String>>(&self, frame: &str, stamp: &Stamp, parent_chain: F) {
+        let queues: Vec<Arc<WatchQueue>> = {
+            let mut watchers = self.watchers.lock().unwrap();
+            let queues: Vec<Arc<WatchQueue>> = match watchers.get(frame) {
+                Some(registered) => registered.iter().filter_map(|x| x.upgrade()).collect(),
+                None => Vec::new()
+            };
+            if !queues.is_empty() {
+                let chain = parent_chain();
+                for queue in queues.iter() {
+                    add_watcher(&mut watchers, &chain, queue);
+                }
+            }
+            queues
+        };
+        for queue in queues {
+            queue.push(frame, stamp);
+        }
+        let wakers = {
+            let mut by_frame = self.by_frame.lock().unwrap();
+            let wakers = match by_frame.remove(frame) {
+                Some(wakers) => wakers,
+                None => return
+            };
+            // a woken lookup registers again if it is still pending
+            for &(id, _) in wakers.iter() {
+                remove_id(&mut by_frame, id);
+            }
+            wakers
+        };
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn pending_frames(&self) -> usize {
+        self.by_frame.lock().unwrap().len()
+    }
+
+    pub fn pending_timeouts(&self) -> usize {
+        self.timeouts.lock().unwrap().len()
+    }
+
+    // The clock may be simulated, so deadlines are checked by polling it
+    // from a helper thread that lives as long as the waiters do
+    pub fn register_timeout(self: &Arc<Self>, id: u64, deadline: i64, waker: &Waker, clock: Arc<dyn Clock>) {
+        let mut timer_running = self.timer_running.lock().unwrap();
+        self.timeouts.lock().unwrap().insert(id, (deadline, waker.clone()));
+        if *timer_running {
+            return;
+        }
+        *timer_running = true;
+        let waiters: Weak<TransformWaiters> = Arc::downgrade(self);
+        std::thread::spawn(move || {
+            while let Some(waiters) = waiters.upgrade() {
+                {
+                    let mut timer_running = waiters.timer_running.lock().unwrap();
+                    if !waiters.wake_expired(clock.now().nanos()) {
+                        *timer_running = false;
+                        return;
+                    }
+                }
+                drop(waiters);
+                std::thread::sleep(TIMER_PERIOD);
+            }
+        });
+    }
+
+    // returns whether there are still deadlines to watch
+    fn wake_expired(&self, now: i64) -> bool {
+        let mut timeouts = self.timeouts.lock().unwrap();
+        let expired: Vec<u64> = timeouts.iter().filter(|x| (x.1).0 <= now).map(|x| *x.0).collect();
+        for id in expired {
+            if let Some((_, waker)) = timeouts.remove(&id) {
+                waker.wake();
+            }
+        }
+        !timeouts.is_empty()
+    }
+
+}
+
+fn add_watcher(watchers: &mut HashMap<String, Vec<Weak<WatchQueue>>>, frames: &[String], queue: &Arc<WatchQueue>) {
+    for frame in frames.iter() {
+        let registered = watchers.entry(frame.clone()).or_insert_with(Vec::new);
+        registered.retain(|x| x.strong_count() > 0);
+        if !registered.iter().any(|x| x.upgrade().map_or(false, |x| Arc::ptr_eq(&x, queue))) {
+            registered.push(Arc::downgrade(queue));
+        }
+    }
+}
+
+fn remove_id(by_frame: &mut HashMap<String, Vec<(u64, Waker)>>, id: u64) {
+    for registered in by_frame.values_mut() {
+        registered.retain(|x| x.0 != id);
+    }
+    by_frame.retain(|_, registered| !registered.is_empty());
+}
+
+// Resolves once the buffer can answer the lookup, or with the last lookup
+// error when `timeout` has passed on the buffer's clock. Works on any executor.
+pub struct LookupTransformFuture {
+    buffer       : Arc<RwLock<Buffer>>,
+    target_frame : String,
+    source_frame : String,
+    time         : Stamp,
+    timeout      : Stamp,
+    deadline     : Option<i64>,
+    // set on the first registration, so that drop does not need the buffer
+    registration : Option<(Arc<TransformWaiters>, u64)>
+}
+
+impl LookupTransformFuture {
+
+    pub fn new(buffer: Arc<RwLock<Buffer>>, target_frame: &str, source_frame: &str,
+               time: &Stamp, timeout: &Stamp) -> LookupTransformFuture {
+        LookupTransformFuture {
+            buffer,
+            target_frame : target_frame.to_string(),
+            source_frame : source_frame.to_string(),
+            time         : time.clone(),
+            timeout      : timeout.clone(),
+            deadline     : None,
+            registration : None
+        }
+    }
+
+}
+
+impl Future for LookupTransformFuture {
+    type Output = Result<Transform, TfError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        // the read lock keeps inserts out until the waker is registered
+        let buffer = this.buffer.read().unwrap();
+        let now = buffer.now().nanos();
+        let deadline = *this.deadline.get_or_insert(now + this.timeout.nanos());
+
+        match buffer.lookup_transform(&this.target_frame, &this.source_frame, &this.time) {
+            Ok(transform) => Poll::Ready(Ok(transform)),
+            Err(err) => {
+                if now >= deadline {
+                    return Poll::Ready(Err(err));
+                }
+                let mut frames = buffer.get_chain_frame_names(&this.target_frame);
+                frames.extend(buffer.get_chain_frame_names(&this.source_frame));
+                let (waiters, id) = this.registration.get_or_insert_with(|| {
+                    let waiters = buffer.get_waiters();
+                    let id = waiters.next_id();
+                    (waiters, id)
+                });
+                waiters.register(*id, &frames, cx.waker());
+                waiters.register_timeout(*id, deadline, cx.waker(), buffer.get_clock());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for LookupTransformFuture {
+    fn drop(&mut self) {
+        if let Some((waiters, id)) = self.registration.take() {
+            waiters.deregister(id);
+        }
+    }
+}
+
+pub fn lookup_transform_async(buffer: Arc<RwLock<Buffer>>, target_frame: &str, source_frame: &str,
+                              time: &Stamp, timeout: &Stamp) -> LookupTransformFuture {
+    LookupTransformFuture::new(buffer, target_frame, source_frame, time, timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{NTranslation3, NQuaternion};
+    use super::super::clock::ManualClock;
+
+    fn make_transform(parent: &str, child: &str, nanos: i64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : NTranslation3::new(1.0, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    #[test]
+    fn test_wakes_on_insert() {
+        let clock = Arc::new(ManualClock::new(Stamp::from_nanos(0)));
+        let buffer = Arc::new(RwLock::new(Buffer::with_clock(clock, None)));
+        buffer.write().unwrap().set_transform(&make_transform("map", "odom", 0), true).unwrap();
+
+        let writer = buffer.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            writer.write().unwrap().set_transform(&make_transform("odom", "base_link", 0), true).unwrap();
+        });
+        let future = lookup_transform_async(buffer.clone(), "map", "base_link",
+                                            &Stamp::from_nanos(0), &Stamp::from_nanos(1_000_000_000));
+        let res = futures::executor::block_on(future).unwrap();
+        handle.join().unwrap();
+        assert!(abs_diff_eq!(2.0, res.translation.vector.x));
+        // the woken waiters were removed
+        assert_eq!(0, buffer.read().unwrap().get_waiters().pending_frames());
+    }
+
+    #[test]
+    fn test_times_out_on_buffer_clock() {
+        let clock = Arc::new(ManualClock::new(Stamp::from_nanos(0)));
+        let buffer = Arc::new(RwLock::new(Buffer::with_clock(clock.clone(), None)));
+
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            clock.set(Stamp::from_nanos(2_000_000_000));
+        });
+        let future = lookup_transform_async(buffer, "map", "base_link",
+                                            &Stamp::from_nanos(0), &Stamp::from_nanos(1_000_000_000));
+        let res = futures::executor::block_on(future);
+        handle.join().unwrap();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_dropped_lookup_deregisters() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let waiters = buffer.read().unwrap().get_waiters();
+        let mut future = Box::pin(lookup_transform_async(buffer.clone(), "map", "no_such_frame",
+                                                         &Stamp::from_nanos(0), &Stamp::from_nanos(60_000_000_000)));
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        for _ in 0..3 {
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+        }
+        assert_eq!(2, waiters.pending_frames());
+        assert_eq!(1, waiters.pending_timeouts());
+
+        drop(future);
+        assert_eq!(0, waiters.pending_frames());
+        assert_eq!(0, waiters.pending_timeouts());
+    }
+
+}
@@ -0,0 +1,244 @@
+use super::transform_storage::Stamp;
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::Buffer;
+use super::tf_message;
+use super::mcap_file;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+const BAG_MAGIC: &[u8] = b"#ROSBAG V2.0\n";
+
+const OP_MESSAGE_DATA : u8 = 0x02;
+const OP_CHUNK        : u8 = 0x05;
+const OP_CONNECTION   : u8 = 0x07;
+
+pub struct BagMessage<'a> {
+    pub topic    : &'a str,
+    pub datatype : &'a str,
+    // node that published the message, empty when the bag does not say
+    pub callerid : &'a str,
+    pub stamp    : Stamp,
+    pub data     : &'a [u8]
+}
+
+struct Connection {
+    topic    : String,
+    datatype : String,
+    callerid : String
+}
+
+fn decode_error(description: &str) -> TfError {
+    TfError::Decode(format!("bag: {}", description))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, TfError> {
+    if pos + 4 > data.len() {
+        return Err(decode_error("record is truncated"));
+    }
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&data[pos..pos + 4]);
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn parse_header(data: &[u8]) -> Result<HashMap<&str, &[u8]>, TfError> {
+    let mut fields = HashMap::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let len = read_u32(data, pos)? as usize;
+        pos += 4;
+        if pos + len > data.len() {
+            return Err(decode_error("header field is truncated"));
+        }
+        let field = &data[pos..pos + len];
+        pos += len;
+        let separator = field.iter().position(|x| *x == b'=')
+            .ok_or_else(|| decode_error("header field without '='"))?;
+        let name = std::str::from_utf8(&field[..separator])
+            .map_err(|_| decode_error("header field name is not utf-8"))?;
+        fields.insert(name, &field[separator + 1..]);
+    }
+    Ok(fields)
+}
+
+fn field<'a>(header: &HashMap<&str, &'a [u8]>, name: &str) -> Result<&'a [u8], TfError> {
+    header.get(name).cloned().ok_or_else(|| decode_error(&format!("record has no '{}' field", name)))
+}
+
+fn string_field(header: &HashMap<&str, &[u8]>, name: &str) -> Result<String, TfError> {
+    String::from_utf8(field(header, name)?.to_vec()).map_err(|_| decode_error("field is not utf-8"))
+}
+
+// Walks the records in `data`, descending into uncompressed chunks
+fn read_records<F>(data: &[u8], connections: &mut HashMap<u32, Connection>, callback: &mut F) -> Result<(), TfError>
+    where F: FnMut(&BagMessage) -> Result<(), TfError>
+{
+    let mut pos = 0;
+    while pos < data.len() {
+        let header_len = read_u32(data, pos)? as usize;
+        let header_start = pos + 4;
+        let data_len = read_u32(data, header_start + header_len)? as usize;
+        let data_start = header_start + header_len + 4;
+        if data_start + data_len > data.len() {
+            return Err(decode_error("record is truncated"));
+        }
+        let header = parse_header(&data[header_start..header_start + header_len])?;
+        let record_data = &data[data_start..data_start + data_len];
+        pos = data_start + data_len;
+
+        let op = field(&header, "op")?;
+        match op.first() {
+            Some(&OP_CHUNK) => {
+                let compression = string_field(&header, "compression")?;
+                if compression != "none" {
+                    return Err(decode_error(&format!("chunks compressed with {} are not supported", compression)));
+                }
+                read_records(record_data, connections, callback)?;
+            },
+            Some(&OP_CONNECTION) => {
+                let conn = read_u32(field(&header, "conn")?, 0)?;
+                let connection_header = parse_header(record_data)?;
+                connections.insert(conn, Connection {
+                    topic    : string_field(&header, "topic")?,
+                    datatype : string_field(&connection_header, "type")?,
+                    callerid : string_field(&connection_header, "callerid").unwrap_or_default()
+                });
+            },
+            Some(&OP_MESSAGE_DATA) => {
+                let conn = read_u32(field(&header, "conn")?, 0)?;
+                let time = field(&header, "time")?;
+                let stamp_nanos = read_u32(time, 0)? as i64 * 1_000_000_000 + read_u32(time, 4)? as i64;
+                let connection = connections.get(&conn)
+                    .ok_or_else(|| decode_error(&format!("message on unknown connection {}", conn)))?;
+                callback(&BagMessage {
+                    topic    : &connection.topic,
+                    datatype : &connection.datatype,
+                    callerid : &connection.callerid,
+                    stamp    : Stamp::from_nanos(stamp_nanos),
+                    data     : record_data
+                })?;
+            },
+            // bag header, index data and chunk info only help random access
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub fn for_each_message<F>(data: &[u8], mut callback: F) -> Result<(), TfError>
+    where F: FnMut(&BagMessage) -> Result<(), TfError>
+{
+    if !data.starts_with(BAG_MAGIC) {
+        return Err(decode_error("not a ROS bag v2.0 file"));
+    }
+    let mut connections = HashMap::new();
+    read_records(&data[BAG_MAGIC.len()..], &mut connections, &mut callback)
+}
+
+pub fn read_bag_bytes(data: &[u8], buffer: &mut Buffer) -> Result<usize, TfError> {
+    let mut inserted = 0;
+    for_each_message(data, |message| {
+        if !tf_message::is_tf_message_datatype(message.datatype) {
+            return Ok(());
+        }
+        let is_static = mcap_file::is_static_topic(message.topic);
+        for transform in tf_message::decode_ros1(message.data)?.iter() {
+            if message.callerid.is_empty() {
+                buffer.set_transform(transform, is_static)?;
+            } else {
+                buffer.set_transform_with_authority(transform, message.callerid, is_static)?;
+            }
+            inserted += 1;
+        }
+        Ok(())
+    })?;
+    Ok(inserted)
+}
+
+pub fn read_bag<P: AsRef<Path>>(path: P, buffer: &mut Buffer) -> Result<usize, TfError> {
+    read_bag_bytes(&std::fs::read(path)?, buffer)
+}
+
+// Loads either a ROS bag or an MCAP file, telling them apart by their magic
+pub fn read_recording<P: AsRef<Path>>(path: P, buffer: &mut Buffer) -> Result<usize, TfError> {
+    let data = std::fs::read(path)?;
+    if data.starts_with(BAG_MAGIC) {
+        read_bag_bytes(&data, buffer)
+    } else {
+        mcap_file::read_mcap_bytes(&data, buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{NTranslation3, NQuaternion};
+    use super::super::tf_buffer::tf::Transform;
+
+    fn encode_fields(fields: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut header = Vec::new();
+        for (name, value) in fields.iter() {
+            header.extend_from_slice(&((name.len() + 1 + value.len()) as u32).to_le_bytes());
+            header.extend_from_slice(name.as_bytes());
+            header.push(b'=');
+            header.extend_from_slice(value);
+        }
+        header
+    }
+
+    fn push_record(out: &mut Vec<u8>, fields: &[(&str, &[u8])], data: &[u8]) {
+        let header = encode_fields(fields);
+        out.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    fn make_tf_message(x: f64, nanos: i64) -> Vec<u8> {
+        tf_message::encode_ros1(&[Transform {
+            frame_id       : "odom".to_string(),
+            child_frame_id : "base_link".to_string(),
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(nanos)
+        }])
+    }
+
+    fn make_bag() -> Vec<u8> {
+        let connection_header = encode_fields(&[("topic", b"/tf"), ("type", b"tf2_msgs/TFMessage"),
+                                                ("callerid", b"/odometry")]);
+
+        let mut chunk = Vec::new();
+        push_record(&mut chunk, &[("op", &[OP_CONNECTION]), ("conn", &0u32.to_le_bytes()), ("topic", b"/tf")],
+                    &connection_header);
+        for i in 1..3u32 {
+            let mut time = Vec::new();
+            time.extend_from_slice(&i.to_le_bytes());
+            time.extend_from_slice(&0u32.to_le_bytes());
+            push_record(&mut chunk, &[("op", &[OP_MESSAGE_DATA]), ("conn", &0u32.to_le_bytes()), ("time", &time)],
+                        &make_tf_message(i as f64, i as i64 * 1_000_000_000));
+        }
+
+        let mut bag = BAG_MAGIC.to_vec();
+        push_record(&mut bag, &[("op", &[0x03])], &[b' '; 16]);
+        push_record(&mut bag, &[("op", &[OP_CHUNK]), ("compression", b"none"),
+                                ("size", &(chunk.len() as u32).to_le_bytes())], &chunk);
+        bag
+    }
+
+    #[test]
+    fn test_read_bag() {
+        let mut buffer = Buffer::new();
+        assert_eq!(2, read_bag_bytes(&make_bag(), &mut buffer).unwrap());
+        let res = buffer.lookup_transform("odom", "base_link", &Stamp::from_nanos(1_500_000_000)).unwrap();
+        assert!(abs_diff_eq!(1.5, res.translation.vector.x));
+        assert_eq!("/odometry", buffer.get_frame_statistics()[0].authority);
+    }
+
+    #[test]
+    fn test_not_a_bag() {
+        let mut buffer = Buffer::new();
+        assert!(read_bag_bytes(b"garbage", &mut buffer).is_err());
+    }
+
+}
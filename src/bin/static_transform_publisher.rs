@@ -0,0 +1,49 @@
+use rosrust_tf::broadcaster::StaticTransformBroadcaster;
+use rosrust_tf::static_transform_publisher::{self, USAGE};
+use rosrust_tf::transform_storage::Stamp;
+use rosrust_tf::{TfError, Transform};
+
+use std::process;
+
+fn load_transforms(args: &[String]) -> Result<Vec<Transform>, TfError> {
+    match args.first().map(|x| x.as_str()) {
+        Some("--file") | Some("-f") if args.len() == 2 => static_transform_publisher::load_yaml_file(&args[1]),
+        _ => Ok(vec![static_transform_publisher::parse_args(args)?])
+    }
+}
+
+fn run(args: &[String]) -> Result<(), TfError> {
+    let mut transforms = load_transforms(args)?;
+
+    rosrust::init("static_transform_publisher");
+    let stamp = Stamp::from_nanos(rosrust::now().nanos());
+    for transform in transforms.iter_mut() {
+        transform.stamp = stamp.clone();
+    }
+
+    let mut broadcaster = StaticTransformBroadcaster::new()?;
+    broadcaster.send_transforms(&transforms)?;
+    for transform in transforms.iter() {
+        println!("publishing {} -> {}", transform.frame_id, transform.child_frame_id);
+    }
+
+    // the message is latched, we only need to stay alive
+    while rosrust::is_ok() {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+fn main() {
+    // skip the program name and any ROS remapping arguments
+    let args: Vec<String> = std::env::args().skip(1).filter(|x| !x.contains(":=")).collect();
+    if args.is_empty() || args[0] == "--help" || args[0] == "-h" {
+        println!("usage:\n{}", USAGE);
+        return;
+    }
+
+    if let Err(err) = run(&args) {
+        eprintln!("static_transform_publisher: {}", err);
+        process::exit(1);
+    }
+}
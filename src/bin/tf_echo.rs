@@ -0,0 +1,127 @@
+use clap::{App, Arg};
+use rosrust_tf::bag_file;
+use rosrust_tf::listener::TransformListener;
+use rosrust_tf::transform_storage::Stamp;
+use rosrust_tf::{Buffer, TfError, Transform};
+
+use std::process;
+
+fn print_transform(transform: &Transform) {
+    let t = &transform.translation.vector;
+    let q = &transform.rotation.coords;
+    let (roll, pitch, yaw) = transform.rotation.euler_angles();
+    let matrix = nalgebra::Isometry3::from_parts(transform.translation, transform.rotation).to_homogeneous();
+
+    println!("At time {:.6}", transform.stamp.nanos() as f64 * 1.0e-9);
+    println!("- Translation: [{:.3}, {:.3}, {:.3}]", t.x, t.y, t.z);
+    println!("- Rotation: in Quaternion [{:.3}, {:.3}, {:.3}, {:.3}]", q.x, q.y, q.z, q.w);
+    println!("            in RPY (radian) [{:.3}, {:.3}, {:.3}]", roll, pitch, yaw);
+    println!("            in RPY (degree) [{:.3}, {:.3}, {:.3}]",
+             roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees());
+    println!("- Matrix:");
+    for row in 0..4 {
+        println!("  {:7.3} {:7.3} {:7.3} {:7.3}",
+                 matrix[(row, 0)], matrix[(row, 1)], matrix[(row, 2)], matrix[(row, 3)]);
+    }
+}
+
+// live, frames that do not exist yet may still arrive on /tf
+fn is_fatal(err: &TfError, live: bool) -> bool {
+    match err {
+        TfError::ConnectivityError(_) => true,
+        TfError::LookupError(_) => !live,
+        _ => false
+    }
+}
+
+fn echo(source_frame: &str, target_frame: &str, time: &Stamp, live: bool,
+        lookup: &dyn Fn(&Stamp) -> Result<Transform, TfError>) {
+    match lookup(time) {
+        Ok(transform) => print_transform(&transform),
+        Err(ref err) if is_fatal(err, live) => {
+            eprintln!("Failure at {}: {}", time.nanos() as f64 * 1.0e-9, err);
+            eprintln!("Could not transform from '{}' to '{}'", source_frame, target_frame);
+            process::exit(1);
+        },
+        Err(err) => eprintln!("Failure at {}: {}", time.nanos() as f64 * 1.0e-9, err)
+    }
+}
+
+fn echo_recording(path: &str, source_frame: &str, target_frame: &str, rate: f64) -> Result<(), TfError> {
+    let mut buffer = Buffer::new();
+    bag_file::read_recording(path, &mut buffer)?;
+
+    let stamps: Vec<i64> = buffer.get_all_transforms().iter()
+        .filter(|x| !x.1)
+        .map(|x| x.0.stamp.nanos())
+        .collect();
+    let lookup = |time: &Stamp| buffer.lookup_transform(source_frame, target_frame, time);
+    match (stamps.iter().min(), stamps.iter().max()) {
+        (Some(&start), Some(&end)) => {
+            let period = (1.0e9 / rate) as i64;
+            let mut time = start;
+            while time <= end {
+                echo(source_frame, target_frame, &Stamp::from_nanos(time), false, &lookup);
+                time += period;
+            }
+        },
+        // only static frames, the time does not matter
+        _ => echo(source_frame, target_frame, &Stamp::from_nanos(0), false, &lookup)
+    }
+    Ok(())
+}
+
+fn echo_live(source_frame: &str, target_frame: &str, rate: f64) -> Result<(), TfError> {
+    rosrust::init("tf_echo");
+    let listener = TransformListener::new()?;
+    // like tf2's tf_echo, at the newest time every link has data for
+    let lookup = |_: &Stamp| {
+        let buffer = listener.buffer.read().unwrap();
+        let time = buffer.get_latest_common_time(source_frame, target_frame)?;
+        buffer.lookup_transform(source_frame, target_frame, &time)
+    };
+    let mut rate = rosrust::rate(rate);
+    while rosrust::is_ok() {
+        rate.sleep();
+        echo(source_frame, target_frame, &Stamp::from_nanos(0), true, &lookup);
+    }
+    Ok(())
+}
+
+fn main() {
+    let matches = App::new("tf_echo")
+        .about("Prints the transform from source_frame to target_frame")
+        .arg(Arg::with_name("source_frame").required(true))
+        .arg(Arg::with_name("target_frame").required(true))
+        .arg(Arg::with_name("rate")
+             .short("r")
+             .long("rate")
+             .takes_value(true)
+             .default_value("1.0")
+             .help("echo rate in Hz"))
+        .arg(Arg::with_name("bag")
+             .long("bag")
+             .takes_value(true)
+             .help("read transforms from a ROS bag or MCAP file instead of /tf"))
+        .get_matches();
+
+    let source_frame = matches.value_of("source_frame").unwrap();
+    let target_frame = matches.value_of("target_frame").unwrap();
+    let rate = match matches.value_of("rate").unwrap().parse::<f64>() {
+        // above 1 GHz the period rounds down to zero nanoseconds
+        Ok(rate) if rate > 0.0 && rate <= 1.0e9 => rate,
+        _ => {
+            eprintln!("rate must be a positive number of at most 1e9 Hz");
+            process::exit(2);
+        }
+    };
+
+    let res = match matches.value_of("bag") {
+        Some(path) => echo_recording(path, source_frame, target_frame, rate),
+        None => echo_live(source_frame, target_frame, rate)
+    };
+    if let Err(err) = res {
+        eprintln!("tf_echo: {}", err);
+        process::exit(1);
+    }
+}
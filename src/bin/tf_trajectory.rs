@@ -0,0 +1,104 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use rosrust_tf::bag_file;
+use rosrust_tf::trajectory::{self, CsvLayout, TrajectoryFormat};
+use rosrust_tf::transform_storage::Stamp;
+use rosrust_tf::{Buffer, TfError};
+
+use std::process;
+
+fn parse_seconds(matches: &ArgMatches, name: &str, default: i64) -> Result<Stamp, TfError> {
+    match matches.value_of(name) {
+        Some(value) => value.parse::<f64>()
+            .map(|x| Stamp::from_nanos((x * 1.0e9).round() as i64))
+            .map_err(|_| TfError::InvalidArgument(format!("{} must be a time in seconds", name))),
+        None => Ok(Stamp::from_nanos(default))
+    }
+}
+
+fn export(matches: &ArgMatches) -> Result<(), TfError> {
+    let mut buffer = Buffer::new();
+    bag_file::read_recording(matches.value_of("bag").unwrap(), &mut buffer)?;
+
+    let mut format: TrajectoryFormat = matches.value_of("format").unwrap().parse()?;
+    if let TrajectoryFormat::Csv(ref mut layout) = format {
+        if let Some(columns) = matches.value_of("columns") {
+            *layout = CsvLayout::from_columns(columns)?;
+        }
+    }
+    let period = match matches.value_of("rate") {
+        Some(rate) => match rate.parse::<f64>() {
+            Ok(rate) if rate > 0.0 => Some(Stamp::from_nanos((1.0e9 / rate) as i64)),
+            _ => return Err(TfError::InvalidArgument("rate must be a positive number".to_string()))
+        },
+        None => None
+    };
+    let start = parse_seconds(matches, "start", 0)?;
+    let end = parse_seconds(matches, "end", i64::max_value())?;
+
+    let trajectory = trajectory::sample_trajectory(&buffer, matches.value_of("reference_frame").unwrap(),
+                                                   matches.value_of("frame").unwrap(), &start, &end, period.as_ref())?;
+    if let Some(path) = matches.value_of("times") {
+        trajectory::export_times(path, &trajectory)?;
+    }
+    match matches.value_of("output") {
+        Some(path) => trajectory::export_trajectory(path, &trajectory, &format),
+        None => trajectory::write_trajectory(std::io::stdout().lock(), &trajectory, &format)
+    }
+}
+
+fn main() {
+    let matches = App::new("tf_trajectory")
+        .about("Works with trajectories of tf frames")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("export")
+            .about("Writes the pose of frame in reference_frame over time")
+            .arg(Arg::with_name("reference_frame").required(true))
+            .arg(Arg::with_name("frame").required(true))
+            .arg(Arg::with_name("bag")
+                 .long("bag")
+                 .takes_value(true)
+                 .required(true)
+                 .help("ROS bag or MCAP file to read transforms from"))
+            .arg(Arg::with_name("format")
+                 .short("f")
+                 .long("format")
+                 .takes_value(true)
+                 .possible_values(&["tum", "kitti", "csv"])
+                 .default_value("tum"))
+            .arg(Arg::with_name("columns")
+                 .long("columns")
+                 .takes_value(true)
+                 .help("csv columns, e.g. time,x,y,z,qx,qy,qz,qw,roll,pitch,yaw,time_ns"))
+            .arg(Arg::with_name("rate")
+                 .short("r")
+                 .long("rate")
+                 .takes_value(true)
+                 .help("sampling rate in Hz, the samples of frame by default"))
+            .arg(Arg::with_name("start")
+                 .long("start")
+                 .takes_value(true)
+                 .help("first time in seconds"))
+            .arg(Arg::with_name("end")
+                 .long("end")
+                 .takes_value(true)
+                 .help("last time in seconds"))
+            .arg(Arg::with_name("output")
+                 .short("o")
+                 .long("output")
+                 .takes_value(true)
+                 .help("file to write, standard output by default"))
+            .arg(Arg::with_name("times")
+                 .long("times")
+                 .takes_value(true)
+                 .help("file to write the time of each pose to, e.g. the times.txt of a KITTI trajectory")))
+        .get_matches();
+
+    let res = match matches.subcommand() {
+        ("export", Some(matches)) => export(matches),
+        _ => unreachable!()
+    };
+    if let Err(err) = res {
+        eprintln!("tf_trajectory: {}", err);
+        process::exit(1);
+    }
+}
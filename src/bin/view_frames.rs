@@ -0,0 +1,69 @@
+use clap::{App, Arg};
+use rosrust_tf::bag_file;
+use rosrust_tf::listener::TransformListener;
+use rosrust_tf::transform_storage::Stamp;
+use rosrust_tf::{Buffer, TfError};
+
+use std::process;
+
+fn frames_from_recording(path: &str) -> Result<String, TfError> {
+    let mut buffer = Buffer::new();
+    bag_file::read_recording(path, &mut buffer)?;
+    // delays are relative to the end of the recording
+    let end = buffer.get_frame_statistics().iter()
+        .filter(|x| !x.is_static)
+        .map(|x| x.most_recent.nanos())
+        .max()
+        .unwrap_or(0);
+    Ok(buffer.all_frames_as_dot(&Stamp::from_nanos(end)))
+}
+
+fn frames_from_live(duration: f64) -> Result<String, TfError> {
+    rosrust::init("view_frames");
+    let listener = TransformListener::new()?;
+    println!("Listening to /tf for {} seconds", duration);
+    std::thread::sleep(std::time::Duration::from_millis((duration * 1000.0) as u64));
+    let now = Stamp::from_nanos(rosrust::now().nanos());
+    let buffer = listener.buffer.read().unwrap();
+    Ok(buffer.all_frames_as_dot(&now))
+}
+
+fn main() {
+    let matches = App::new("view_frames")
+        .about("Writes the tf frame tree as a Graphviz DOT file")
+        .arg(Arg::with_name("duration")
+             .short("d")
+             .long("duration")
+             .takes_value(true)
+             .default_value("5.0")
+             .help("seconds to listen to /tf"))
+        .arg(Arg::with_name("bag")
+             .long("bag")
+             .takes_value(true)
+             .help("read transforms from a ROS bag or MCAP file instead of /tf"))
+        .arg(Arg::with_name("output")
+             .short("o")
+             .long("output")
+             .takes_value(true)
+             .default_value("frames.gv")
+             .help("DOT file to write"))
+        .get_matches();
+
+    let res = match matches.value_of("bag") {
+        Some(path) => frames_from_recording(path),
+        None => match matches.value_of("duration").unwrap().parse::<f64>() {
+            Ok(duration) if duration > 0.0 => frames_from_live(duration),
+            _ => Err(TfError::InvalidArgument("duration must be a positive number".to_string()))
+        }
+    };
+
+    let output = matches.value_of("output").unwrap();
+    let res = res.and_then(|dot| std::fs::write(output, dot).map_err(TfError::from));
+    match res {
+        Ok(()) => println!("Wrote {}, render it with: dot -Tsvg {} -o frames.svg", output, output),
+        Err(err) => {
+            eprintln!("view_frames: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::Transform;
+use super::transport::TransformTransport;
+#[cfg(feature = "ros")]
+use super::transport::RosTransformTransport;
+
+use std::sync::Arc;
+
+pub struct TransformBroadcaster {
+    transport : Arc<dyn TransformTransport>
+}
+
+impl TransformBroadcaster {
+
+    #[cfg(feature = "ros")]
+    pub fn new() -> Result<TransformBroadcaster, TfError> {
+        let transport = RosTransformTransport::new();
+        transport.advertise(false)?;
+        Ok(TransformBroadcaster::with_transport(Arc::new(transport)))
+    }
+
+    pub fn with_transport(transport: Arc<dyn TransformTransport>) -> TransformBroadcaster {
+        TransformBroadcaster {
+            transport
+        }
+    }
+
+    pub fn send_transforms(&mut self, transforms: &[Transform]) -> Result<(), TfError> {
+        self.transport.publish(transforms, false)
+    }
+
+}
+
+// Publishes latched on /tf_static. Like tf2_ros, every frame sent so far is
+// kept and the whole set is republished, since a latched topic only
+// remembers the last message.
+pub struct StaticTransformBroadcaster {
+    transport  : Arc<dyn TransformTransport>,
+    transforms : Vec<Transform>
+}
+
+impl StaticTransformBroadcaster {
+
+    #[cfg(feature = "ros")]
+    pub fn new() -> Result<StaticTransformBroadcaster, TfError> {
+        let transport = RosTransformTransport::new();
+        transport.advertise(true)?;
+        Ok(StaticTransformBroadcaster::with_transport(Arc::new(transport)))
+    }
+
+    pub fn with_transport(transport: Arc<dyn TransformTransport>) -> StaticTransformBroadcaster {
+        StaticTransformBroadcaster {
+            transport,
+            transforms : Vec::new()
+        }
+    }
+
+    pub fn send_transforms(&mut self, transforms: &[Transform]) -> Result<(), TfError> {
+        for transform in transforms.iter() {
+            match self.transforms.iter_mut().find(|x| x.child_frame_id == transform.child_frame_id) {
+                Some(existing) => *existing = transform.clone(),
+                None => self.transforms.push(transform.clone())
+            }
+        }
+        self.transport.publish(&self.transforms, true)
+    }
+
+}
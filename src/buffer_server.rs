@@ -0,0 +1,573 @@
+use super::transform_storage::Stamp;
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{self, Buffer, Transform, TransformLookup};
+use super::msg_conversion::{transform_from_msg, transform_to_msg};
+use super::msg::actionlib_msgs::{GoalID, GoalStatus, GoalStatusArray};
+use super::msg::tf2_msgs::{LookupTransformGoal, LookupTransformResult, TF2Error,
+                           LookupTransformActionGoal, LookupTransformActionResult,
+                           LookupTransformActionFeedback};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+// same action namespace as tf2_ros
+pub const DEFAULT_SERVER_NAME: &str = "tf2_buffer_server";
+
+// how long a client waits for an answer on top of the lookup timeout
+const ANSWER_MARGIN: Duration = Duration::from_secs(2);
+
+// the defaults of an actionlib server: the status list is published at
+// 5 Hz and finished goals stay in it for 5 seconds
+const STATUS_PERIOD: Duration = Duration::from_millis(200);
+const STATUS_LIST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// goals waiting for a worker beyond this are rejected
+const MAX_QUEUED_GOALS: usize = 100;
+pub const DEFAULT_WORKER_COUNT: usize = 4;
+
+fn ros_error(err: rosrust::error::Error) -> TfError {
+    TfError::Ros(err.to_string())
+}
+
+fn error_code(err: &TfError) -> u8 {
+    match err {
+        TfError::LookupError(_) | TfError::TransformNotFound | TfError::NoParent => TF2Error::LOOKUP_ERROR,
+        TfError::ConnectivityError(_) => TF2Error::CONNECTIVITY_ERROR,
+        TfError::ExtrapolationError1 | TfError::ExtrapolationError2 | TfError::ExtrapolationError3 |
+        TfError::ExtrapolationError(_) => TF2Error::EXTRAPOLATION_ERROR,
+        TfError::InvalidArgument(_) => TF2Error::INVALID_ARGUMENT_ERROR,
+        _ => TF2Error::TRANSFORM_ERROR
+    }
+}
+
+fn error_from_msg(error: &TF2Error) -> TfError {
+    let description = error.error_string.clone();
+    match error.error {
+        TF2Error::CONNECTIVITY_ERROR => TfError::ConnectivityError(description),
+        TF2Error::EXTRAPOLATION_ERROR => TfError::ExtrapolationError(description),
+        TF2Error::INVALID_ARGUMENT_ERROR => TfError::InvalidArgument(description),
+        TF2Error::TIMEOUT_ERROR => TfError::LookupError(format!("timed out: {}", description)),
+        _ => TfError::LookupError(description)
+    }
+}
+
+pub fn make_goal(target_frame: &str, source_frame: &str, time: &Stamp, timeout: &Stamp) -> LookupTransformGoal {
+    let mut goal = LookupTransformGoal::default();
+    goal.target_frame = target_frame.to_string();
+    goal.source_frame = source_frame.to_string();
+    goal.source_time = rosrust::Time::from_nanos(time.nanos());
+    goal.timeout = rosrust::Duration::from_nanos(timeout.nanos());
+    goal
+}
+
+pub fn transform_from_result(result: &LookupTransformResult) -> Result<Transform, TfError> {
+    if result.error.error == TF2Error::NO_ERROR {
+        Ok(transform_from_msg(&result.transform))
+    } else {
+        Err(error_from_msg(&result.error))
+    }
+}
+
+struct TrackedGoal {
+    status    : GoalStatus,
+    cancelled : Arc<AtomicBool>,
+    // when the goal reached a terminal status
+    finished  : Option<Instant>
+}
+
+// The goals of an action server and their actionlib status
+struct GoalTracker {
+    goals : Mutex<HashMap<String, TrackedGoal>>
+}
+
+impl GoalTracker {
+
+    fn new() -> GoalTracker {
+        GoalTracker {
+            goals : Mutex::new(HashMap::new())
+        }
+    }
+
+    // the flag is set when a client cancels the goal
+    fn add(&self, goal_id: &GoalID) -> Arc<AtomicBool> {
+        let mut status = GoalStatus::default();
+        status.goal_id = goal_id.clone();
+        status.status = GoalStatus::PENDING;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.goals.lock().unwrap().insert(goal_id.id.clone(), TrackedGoal {
+            status,
+            cancelled : cancelled.clone(),
+            finished  : None
+        });
+        cancelled
+    }
+
+    // Same rules as actionlib: an empty id with a zero stamp cancels every
+    // goal, an id cancels that goal and a stamp every goal sent up to it
+    fn cancel(&self, cancel: &GoalID) {
+        let cancel_all = cancel.id.is_empty() && cancel.stamp.nanos() == 0;
+        for goal in self.goals.lock().unwrap().values_mut() {
+            let status = &mut goal.status;
+            if goal.finished.is_some() || !(cancel_all || status.goal_id.id == cancel.id ||
+                                            (cancel.stamp.nanos() != 0 && status.goal_id.stamp.nanos() <= cancel.stamp.nanos())) {
+                continue;
+            }
+            goal.cancelled.store(true, Ordering::SeqCst);
+            status.status = match status.status {
+                GoalStatus::PENDING => GoalStatus::RECALLING,
+                GoalStatus::ACTIVE => GoalStatus::PREEMPTING,
+                other => other
+            };
+        }
+    }
+
+    fn start(&self, id: &str) {
+        if let Some(goal) = self.goals.lock().unwrap().get_mut(id) {
+            if goal.status.status == GoalStatus::PENDING {
+                goal.status.status = GoalStatus::ACTIVE;
+            }
+        }
+    }
+
+    fn finish(&self, id: &str, status: u8, text: &str) -> GoalStatus {
+        let mut goals = self.goals.lock().unwrap();
+        match goals.get_mut(id) {
+            Some(goal) => {
+                goal.status.status = status;
+                goal.status.text = text.to_string();
+                goal.finished = Some(Instant::now());
+                goal.status.clone()
+            },
+            None => GoalStatus::default()
+        }
+    }
+
+    // also forgets the goals that finished long enough ago
+    fn status_list(&self) -> Vec<GoalStatus> {
+        let mut goals = self.goals.lock().unwrap();
+        goals.retain(|_, goal| goal.finished.map_or(true, |x| x.elapsed() < STATUS_LIST_TIMEOUT));
+        goals.values().map(|goal| goal.status.clone()).collect()
+    }
+
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// A fixed number of threads taking jobs from a bounded queue
+struct WorkerPool {
+    sender : Mutex<SyncSender<Job>>
+}
+
+impl WorkerPool {
+
+    fn new(workers: usize, queue: usize) -> WorkerPool {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers.max(1) {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || loop {
+                // the pool was dropped once the channel hangs up
+                let job = match receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return
+                };
+                job();
+            });
+        }
+        WorkerPool {
+            sender : Mutex::new(sender)
+        }
+    }
+
+    // false if the queue is full
+    fn execute(&self, job: Job) -> bool {
+        match self.sender.lock().unwrap().try_send(job) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => false
+        }
+    }
+
+}
+
+fn send_result(tracker: &GoalTracker, publisher: &Mutex<rosrust::Publisher<LookupTransformActionResult>>,
+               id: &str, result: LookupTransformResult, status: u8) {
+    let mut answer = LookupTransformActionResult::default();
+    answer.header.stamp = rosrust::now();
+    answer.status = tracker.finish(id, status, &result.error.error_string);
+    answer.result = result;
+    // a client that went away is not the server's problem
+    let _ = publisher.lock().unwrap().send(answer);
+}
+
+// Answers LookupTransform goals from a shared Buffer
+pub struct BufferServer {
+    buffer : Arc<RwLock<Buffer>>
+}
+
+impl BufferServer {
+
+    pub fn new(buffer: Arc<RwLock<Buffer>>) -> BufferServer {
+        BufferServer {
+            buffer
+        }
+    }
+
+    pub fn handle_goal(&self, goal: &LookupTransformGoal) -> LookupTransformResult {
+        self.handle_cancellable_goal(goal, &AtomicBool::new(false)).unwrap()
+    }
+
+    // None if the goal was cancelled while waiting for the transform
+    fn handle_cancellable_goal(&self, goal: &LookupTransformGoal, cancelled: &AtomicBool) -> Option<LookupTransformResult> {
+        let mut result = LookupTransformResult::default();
+        let res = if goal.advanced {
+            Err(TfError::InvalidArgument("lookups through a fixed frame are not supported".to_string()))
+        } else {
+            let time = Stamp::from_nanos(goal.source_time.nanos());
+            let timeout = Stamp::from_nanos(goal.timeout.nanos());
+            if timeout.nanos() > 0 {
+                tf::wait_for_transform_cancellable(&self.buffer, &goal.target_frame, &goal.source_frame,
+                                                   &time, &timeout, cancelled)?
+            } else {
+                self.buffer.read().unwrap().lookup_transform(&goal.target_frame, &goal.source_frame, &time)
+            }
+        };
+        match res {
+            Ok(transform) => result.transform = transform_to_msg(&transform),
+            Err(err) => {
+                result.error.error = error_code(&err);
+                result.error.error_string = err.to_string();
+            }
+        }
+        Some(result)
+    }
+
+    // Serves the action like a tf2_ros server: goals are answered by a
+    // few worker threads, the status list is published as a heartbeat and
+    // goals can be cancelled. There is no feedback, the topic is only
+    // advertised so that actionlib clients see the server as connected.
+    pub fn advertise(self: &Arc<Self>, name: &str) -> Result<BufferServerHandle, TfError> {
+        self.advertise_with_workers(name, DEFAULT_WORKER_COUNT)
+    }
+
+    pub fn advertise_with_workers(self: &Arc<Self>, name: &str, workers: usize) -> Result<BufferServerHandle, TfError> {
+        let result_publisher = Arc::new(Mutex::new(rosrust::publish(&format!("{}/result", name)).map_err(ros_error)?));
+        let status_publisher = rosrust::publish::<GoalStatusArray>(&format!("{}/status", name)).map_err(ros_error)?;
+        let feedback_publisher = rosrust::publish::<LookupTransformActionFeedback>(&format!("{}/feedback", name))
+            .map_err(ros_error)?;
+        let tracker = Arc::new(GoalTracker::new());
+        let pool = Arc::new(WorkerPool::new(workers, MAX_QUEUED_GOALS));
+
+        let server = self.clone();
+        let goal_tracker = tracker.clone();
+        let goal_subscriber = rosrust::subscribe(&format!("{}/goal", name), move |msg: LookupTransformActionGoal| {
+            let id = msg.goal_id.id.clone();
+            let cancelled = goal_tracker.add(&msg.goal_id);
+            let (server, tracker, publisher) = (server.clone(), goal_tracker.clone(), result_publisher.clone());
+            let accepted = pool.execute(Box::new(move || {
+                let (result, status) = if cancelled.load(Ordering::SeqCst) {
+                    (LookupTransformResult::default(), GoalStatus::RECALLED)
+                } else {
+                    tracker.start(&msg.goal_id.id);
+                    match server.handle_cancellable_goal(&msg.goal, &cancelled) {
+                        Some(result) if result.error.error == TF2Error::NO_ERROR => (result, GoalStatus::SUCCEEDED),
+                        Some(result) => (result, GoalStatus::ABORTED),
+                        None => (LookupTransformResult::default(), GoalStatus::PREEMPTED)
+                    }
+                };
+                send_result(&tracker, &publisher, &msg.goal_id.id, result, status);
+            }));
+            if !accepted {
+                send_result(&goal_tracker, &result_publisher, &id, LookupTransformResult::default(), GoalStatus::REJECTED);
+            }
+        }).map_err(ros_error)?;
+
+        let cancel_tracker = tracker.clone();
+        let cancel_subscriber = rosrust::subscribe(&format!("{}/cancel", name), move |msg: GoalID| {
+            cancel_tracker.cancel(&msg);
+        }).map_err(ros_error)?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let heartbeat_running = running.clone();
+        std::thread::spawn(move || {
+            while heartbeat_running.load(Ordering::SeqCst) && rosrust::is_ok() {
+                let mut msg = GoalStatusArray::default();
+                msg.header.stamp = rosrust::now();
+                msg.status_list = tracker.status_list();
+                let _ = status_publisher.send(msg);
+                std::thread::sleep(STATUS_PERIOD);
+            }
+        });
+
+        Ok(BufferServerHandle {
+            running,
+            _feedback_publisher : feedback_publisher,
+            _goal_subscriber    : goal_subscriber,
+            _cancel_subscriber  : cancel_subscriber
+        })
+    }
+
+}
+
+// Keeps a server advertised for as long as it is alive
+pub struct BufferServerHandle {
+    running             : Arc<AtomicBool>,
+    _feedback_publisher : rosrust::Publisher<LookupTransformActionFeedback>,
+    _goal_subscriber    : rosrust::Subscriber,
+    _cancel_subscriber  : rosrust::Subscriber
+}
+
+impl Drop for BufferServerHandle {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+// How a BufferClient reaches its server
+pub trait LookupTransport: Send + Sync {
+    fn lookup(&self, goal: &LookupTransformGoal) -> Result<LookupTransformResult, TfError>;
+}
+
+// Calls the server directly, for tests and for clients in the same process
+pub struct InProcessTransport {
+    server : Arc<BufferServer>
+}
+
+impl InProcessTransport {
+
+    pub fn new(server: Arc<BufferServer>) -> InProcessTransport {
+        InProcessTransport {
+            server
+        }
+    }
+
+}
+
+impl LookupTransport for InProcessTransport {
+    fn lookup(&self, goal: &LookupTransformGoal) -> Result<LookupTransformResult, TfError> {
+        Ok(self.server.handle_goal(goal))
+    }
+}
+
+// answers by goal id, only for the goals this client sent
+type PendingAnswers = Arc<(Mutex<HashMap<String, Option<LookupTransformResult>>>, Condvar)>;
+
+// Sends goals on the action topics and matches the results by goal id
+pub struct RosTransport {
+    publisher   : Mutex<rosrust::Publisher<LookupTransformActionGoal>>,
+    pending     : PendingAnswers,
+    next_id     : AtomicUsize,
+    _subscriber : rosrust::Subscriber
+}
+
+impl RosTransport {
+
+    pub fn new(name: &str) -> Result<RosTransport, TfError> {
+        let pending: PendingAnswers = Arc::new((Mutex::new(HashMap::new()), Condvar::new()));
+        let subscriber_pending = pending.clone();
+        let subscriber = rosrust::subscribe(&format!("{}/result", name), move |msg: LookupTransformActionResult| {
+            let (answers, answered) = &*subscriber_pending;
+            if let Some(answer) = answers.lock().unwrap().get_mut(&msg.status.goal_id.id) {
+                *answer = Some(msg.result);
+                answered.notify_all();
+            }
+        }).map_err(ros_error)?;
+        Ok(RosTransport {
+            publisher   : Mutex::new(rosrust::publish(&format!("{}/goal", name)).map_err(ros_error)?),
+            pending,
+            next_id     : AtomicUsize::new(0),
+            _subscriber : subscriber
+        })
+    }
+
+}
+
+impl LookupTransport for RosTransport {
+    fn lookup(&self, goal: &LookupTransformGoal) -> Result<LookupTransformResult, TfError> {
+        let mut msg = LookupTransformActionGoal::default();
+        msg.header.stamp = rosrust::now();
+        msg.goal_id.stamp = msg.header.stamp.clone();
+        msg.goal_id.id = format!("{}-{}-{}", rosrust::name(), self.next_id.fetch_add(1, Ordering::SeqCst),
+                                 msg.header.stamp.nanos());
+        msg.goal = goal.clone();
+        let id = msg.goal_id.id.clone();
+
+        let (answers, answered) = &*self.pending;
+        answers.lock().unwrap().insert(id.clone(), None);
+        if let Err(err) = self.publisher.lock().unwrap().send(msg) {
+            answers.lock().unwrap().remove(&id);
+            return Err(ros_error(err));
+        }
+
+        let wait = std::time::Duration::from_nanos(goal.timeout.nanos().max(0) as u64) + ANSWER_MARGIN;
+        let deadline = std::time::Instant::now() + wait;
+        let mut answers = answers.lock().unwrap();
+        loop {
+            if let Some(Some(_)) = answers.get(&id) {
+                return Ok(answers.remove(&id).unwrap().unwrap());
+            }
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                answers.remove(&id);
+                return Err(TfError::Ros(format!("no answer from the buffer server for {}", id)));
+            }
+            answers = answered.wait_timeout(answers, deadline - now).unwrap().0;
+        }
+    }
+}
+
+// Answers lookups from a remote buffer instead of listening to all of /tf
+pub struct BufferClient {
+    transport : Box<dyn LookupTransport>,
+    timeout   : Stamp
+}
+
+impl BufferClient {
+
+    pub fn new(transport: Box<dyn LookupTransport>) -> BufferClient {
+        BufferClient {
+            transport,
+            timeout   : Stamp::from_nanos(0)
+        }
+    }
+
+    pub fn connect(name: &str) -> Result<BufferClient, TfError> {
+        Ok(BufferClient::new(Box::new(RosTransport::new(name)?)))
+    }
+
+    // how long the server may wait for the transform, used by TransformLookup
+    pub fn set_timeout(&mut self, timeout: Stamp) {
+        self.timeout = timeout;
+    }
+
+    pub fn wait_for_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp, timeout: &Stamp) -> Result<Transform, TfError> {
+        let result = self.transport.lookup(&make_goal(target_frame, source_frame, time, timeout))?;
+        transform_from_result(&result)
+    }
+
+}
+
+impl TransformLookup for BufferClient {
+    fn lookup_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> Result<Transform, TfError> {
+        self.wait_for_transform(target_frame, source_frame, time, &self.timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{NTranslation3, NQuaternion};
+
+    fn make_transform(parent: &str, child: &str, x: f64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(0)
+        }
+    }
+
+    fn make_client() -> (Arc<RwLock<Buffer>>, BufferClient) {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let server = Arc::new(BufferServer::new(buffer.clone()));
+        (buffer, BufferClient::new(Box::new(InProcessTransport::new(server))))
+    }
+
+    #[test]
+    fn test_client_lookup() {
+        let (buffer, client) = make_client();
+        buffer.write().unwrap().set_transform(&make_transform("map", "odom", 1.0), true).unwrap();
+        buffer.write().unwrap().set_transform(&make_transform("odom", "base_link", 2.0), true).unwrap();
+
+        let res = client.lookup_transform("map", "base_link", &Stamp::from_nanos(0)).unwrap();
+        assert_eq!("map", res.frame_id);
+        assert_eq!("base_link", res.child_frame_id);
+        assert!(abs_diff_eq!(3.0, res.translation.vector.x));
+        assert!(client.can_transform("base_link", "map", &Stamp::from_nanos(0)));
+    }
+
+    #[test]
+    fn test_client_errors() {
+        let (buffer, client) = make_client();
+        buffer.write().unwrap().set_transform(&make_transform("map", "odom", 1.0), true).unwrap();
+        buffer.write().unwrap().set_transform(&make_transform("world", "robot", 1.0), true).unwrap();
+
+        match client.lookup_transform("map", "laser", &Stamp::from_nanos(0)) {
+            Err(TfError::LookupError(_)) => {},
+            res => panic!("unexpected {:?}", res)
+        }
+        match client.lookup_transform("map", "robot", &Stamp::from_nanos(0)) {
+            Err(TfError::ConnectivityError(_)) => {},
+            res => panic!("unexpected {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_extrapolation_keeps_message() {
+        let mut error = TF2Error::default();
+        error.error = TF2Error::EXTRAPOLATION_ERROR;
+        error.error_string = "lookup would require extrapolation 0.5 s into the future".to_string();
+        let err = error_from_msg(&error);
+        assert_eq!(TF2Error::EXTRAPOLATION_ERROR, error_code(&err));
+        assert_eq!(error.error_string, err.to_string());
+    }
+
+    fn make_goal_id(id: &str, nanos: i64) -> GoalID {
+        let mut goal_id = GoalID::default();
+        goal_id.id = id.to_string();
+        goal_id.stamp = rosrust::Time::from_nanos(nanos);
+        goal_id
+    }
+
+    #[test]
+    fn test_goal_tracker_cancel() {
+        let tracker = GoalTracker::new();
+        let first = tracker.add(&make_goal_id("first", 100));
+        let second = tracker.add(&make_goal_id("second", 200));
+        let third = tracker.add(&make_goal_id("third", 300));
+        tracker.start("first");
+
+        tracker.cancel(&make_goal_id("second", 0));
+        assert!(!first.load(Ordering::SeqCst) && second.load(Ordering::SeqCst) && !third.load(Ordering::SeqCst));
+        // everything sent up to the stamp
+        tracker.cancel(&make_goal_id("", 100));
+        assert!(first.load(Ordering::SeqCst) && !third.load(Ordering::SeqCst));
+        tracker.cancel(&make_goal_id("", 0));
+        assert!(third.load(Ordering::SeqCst));
+
+        let mut statuses: Vec<(String, u8)> = tracker.status_list().into_iter().map(|x| (x.goal_id.id, x.status)).collect();
+        statuses.sort();
+        assert_eq!(vec![("first".to_string(), GoalStatus::PREEMPTING), ("second".to_string(), GoalStatus::RECALLING),
+                        ("third".to_string(), GoalStatus::RECALLING)], statuses);
+
+        assert_eq!(GoalStatus::PREEMPTED, tracker.finish("first", GoalStatus::PREEMPTED, "").status);
+        // finished goals stay in the list for a while
+        assert_eq!(3, tracker.status_list().len());
+    }
+
+    #[test]
+    fn test_worker_pool_is_bounded() {
+        let pool = WorkerPool::new(1, 1);
+        let (started, release) = (Arc::new((Mutex::new(false), Condvar::new())), Arc::new(Mutex::new(())));
+        let guard = release.lock().unwrap();
+        let (job_started, job_release) = (started.clone(), release.clone());
+        assert!(pool.execute(Box::new(move || {
+            *job_started.0.lock().unwrap() = true;
+            job_started.1.notify_all();
+            drop(job_release.lock().unwrap());
+        })));
+        let mut is_started = started.0.lock().unwrap();
+        while !*is_started {
+            is_started = started.1.wait(is_started).unwrap();
+        }
+        drop(is_started);
+        // the only worker is busy, one job fits in the queue
+        assert!(pool.execute(Box::new(|| {})));
+        assert!(!pool.execute(Box::new(|| {})));
+        drop(guard);
+    }
+
+}
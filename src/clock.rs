@@ -0,0 +1,121 @@
+use super::transform_storage::Stamp;
+#[cfg(feature = "ros")]
+use super::time_cache_interface::TfError;
+#[cfg(feature = "ros")]
+use super::msg::rosgraph_msgs::Clock as ClockMsg;
+
+#[cfg(feature = "ros")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Stamp;
+}
+
+pub struct WallClock;
+
+impl Clock for WallClock {
+    fn now(&self) -> Stamp {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Stamp::from_nanos(since_epoch.as_nanos() as i64)
+    }
+}
+
+// Only moves when told to, for tests and offline processing
+pub struct ManualClock {
+    now : Mutex<Stamp>
+}
+
+impl ManualClock {
+
+    pub fn new(now: Stamp) -> ManualClock {
+        ManualClock {
+            now : Mutex::new(now)
+        }
+    }
+
+    pub fn set(&self, now: Stamp) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, step: &Stamp) {
+        let mut now = self.now.lock().unwrap();
+        *now = Stamp::from_nanos(now.nanos() + step.nanos());
+    }
+
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Stamp {
+        self.now.lock().unwrap().clone()
+    }
+}
+
+// Simulated time from /clock, zero until the first message arrives like in roscpp
+#[cfg(feature = "ros")]
+pub struct SimClock {
+    now         : Arc<Mutex<Stamp>>,
+    _subscriber : rosrust::Subscriber
+}
+
+#[cfg(feature = "ros")]
+impl SimClock {
+
+    pub fn new() -> Result<SimClock, TfError> {
+        let now = Arc::new(Mutex::new(Stamp::from_nanos(0)));
+        let subscriber_now = now.clone();
+        let subscriber = rosrust::subscribe("/clock", move |msg: ClockMsg| {
+            *subscriber_now.lock().unwrap() = Stamp::from_nanos(msg.clock.nanos());
+        }).map_err(|err| TfError::Ros(format!("could not subscribe to /clock: {}", err)))?;
+        Ok(SimClock {
+            now,
+            _subscriber : subscriber
+        })
+    }
+
+}
+
+#[cfg(feature = "ros")]
+impl Clock for SimClock {
+    fn now(&self) -> Stamp {
+        self.now.lock().unwrap().clone()
+    }
+}
+
+// The clock a ROS node should use, following the /use_sim_time parameter
+#[cfg(feature = "ros")]
+pub fn ros_clock() -> Result<Arc<dyn Clock>, TfError> {
+    let use_sim_time = rosrust::param("/use_sim_time")
+        .and_then(|param| param.get::<bool>().ok())
+        .unwrap_or(false);
+    if use_sim_time {
+        Ok(Arc::new(SimClock::new()?))
+    } else {
+        Ok(Arc::new(WallClock))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new(Stamp::from_nanos(100));
+        assert_eq!(Stamp::from_nanos(100), clock.now());
+        clock.advance(&Stamp::from_nanos(50));
+        assert_eq!(Stamp::from_nanos(150), clock.now());
+        clock.set(Stamp::from_nanos(10));
+        assert_eq!(Stamp::from_nanos(10), clock.now());
+    }
+
+    #[test]
+    fn test_wall_clock_moves_forward() {
+        let clock = WallClock;
+        let first = clock.now();
+        assert!(first.nanos() > 0);
+        assert!(clock.now() >= first);
+    }
+
+}
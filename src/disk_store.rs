@@ -0,0 +1,485 @@
+use super::transform_storage::{FrameId, TransformStorage, Stamp, NTranslation3, NQuaternion};
+use super::time_cache_interface::{TimeCacheInterface, TimeCache, TfError};
+use super::static_cache::StaticCache;
+use super::tf_buffer::tf::{Buffer, Transform};
+
+use memmap2::MmapMut;
+use nalgebra::Quaternion;
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// File layout, little endian:
+//   header  : magic, capacity as u64
+//   names   : MAX_FRAMES entries of (id u32, length u32, utf-8 name), by id
+//   statics : MAX_FRAMES records, the latest static sample of each child
+//             frame by id, so that dynamic samples never overwrite them
+//   records : `capacity` slots of (sequence u64, parent u32, child u32,
+//             stamp i64, translation xyz and rotation ijkw as f64, flags u32,
+//             crc32 of everything before it), overwritten oldest first.
+//             A record with the reset flag marks a reset of the buffer on
+//             a time jump, the dynamic samples before it are stale.
+const MAGIC: &[u8; 8] = b"TFDISK02";
+const HEADER_SIZE: usize = 64;
+pub const MAX_FRAMES: usize = 4096;
+const NAME_SIZE: usize = 128;
+pub const MAX_NAME_LENGTH: usize = NAME_SIZE - 8;
+const RECORD_SIZE: usize = 96;
+const CRC_OFFSET: usize = 84;
+const STATIC_FLAG: u32 = 1;
+const RESET_FLAG: u32 = 2;
+
+fn decode_error(description: &str) -> TfError {
+    TfError::Decode(format!("disk store: {}", description))
+}
+
+fn put(data: &mut [u8], pos: usize, bytes: &[u8]) {
+    data[pos..pos + bytes.len()].copy_from_slice(bytes);
+}
+
+fn get_u32(data: &[u8], pos: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&data[pos..pos + 4]);
+    u32::from_le_bytes(buf)
+}
+
+fn get_u64(data: &[u8], pos: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&data[pos..pos + 8]);
+    u64::from_le_bytes(buf)
+}
+
+fn is_registered(data: &[u8], id: FrameId) -> bool {
+    id != 0 && id as usize <= MAX_FRAMES && get_u32(data, HEADER_SIZE + (id as usize - 1) * NAME_SIZE) == id
+}
+
+fn statics_offset() -> usize {
+    HEADER_SIZE + MAX_FRAMES * NAME_SIZE
+}
+
+fn records_offset() -> usize {
+    statics_offset() + MAX_FRAMES * RECORD_SIZE
+}
+
+fn file_size(capacity: usize) -> usize {
+    records_offset() + capacity * RECORD_SIZE
+}
+
+fn encode_record(record: &mut [u8], seq: u64, ts: &TransformStorage, flags: u32) {
+    put(record, 0, &seq.to_le_bytes());
+    put(record, 8, &ts.frame_id.to_le_bytes());
+    put(record, 12, &ts.child_frame_id.to_le_bytes());
+    put(record, 16, &ts.stamp.nanos().to_le_bytes());
+    let t = &ts.translation.vector;
+    let q = &ts.rotation.coords;
+    for (i, value) in [t.x, t.y, t.z, q[0], q[1], q[2], q[3]].iter().enumerate() {
+        put(record, 24 + i * 8, &value.to_le_bytes());
+    }
+    put(record, 80, &flags.to_le_bytes());
+    let crc = crc32fast::hash(&record[..CRC_OFFSET]);
+    put(record, CRC_OFFSET, &crc.to_le_bytes());
+}
+
+// None for empty slots and for records torn by a crash in the middle of a write
+fn decode_record(record: &[u8]) -> Option<(u64, TransformStorage, u32)> {
+    let seq = get_u64(record, 0);
+    if seq == 0 || crc32fast::hash(&record[..CRC_OFFSET]) != get_u32(record, CRC_OFFSET) {
+        return None;
+    }
+    let mut values = [0.0; 7];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = f64::from_bits(get_u64(record, 24 + i * 8));
+    }
+    let ts = TransformStorage {
+        frame_id       : get_u32(record, 8),
+        child_frame_id : get_u32(record, 12),
+        translation    : NTranslation3::new(values[0], values[1], values[2]),
+        rotation       : NQuaternion::new_unchecked(Quaternion::new(values[6], values[3], values[4], values[5])),
+        stamp          : Stamp::from_nanos(get_u64(record, 16) as i64)
+    };
+    Some((seq, ts, get_u32(record, 80)))
+}
+
+struct StoreState {
+    map       : MmapMut,
+    next_seq  : u64,
+    // the ring slot written next
+    next_slot : usize,
+    // samples of frames whose names could not be stored
+    dropped   : u64
+}
+
+// A fixed-size file holding the latest `capacity` dynamic samples and the
+// static samples of every frame.
+// Writes go to a shared mapping, so they survive a crash of the process;
+// call flush() to also survive a crash of the machine.
+pub struct DiskStore {
+    path     : PathBuf,
+    capacity : usize,
+    state    : Mutex<StoreState>
+}
+
+impl DiskStore {
+
+    // truncates the file, move away the one of a crash before recording again
+    pub fn create<P: AsRef<Path>>(path: P, capacity: usize) -> Result<Arc<DiskStore>, TfError> {
+        if capacity == 0 {
+            return Err(TfError::InvalidArgument("a disk store needs room for at least one sample".to_string()));
+        }
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path.as_ref())?;
+        file.set_len(file_size(capacity) as u64)?;
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        put(&mut map, 0, MAGIC);
+        put(&mut map, 8, &(capacity as u64).to_le_bytes());
+        Ok(Arc::new(DiskStore {
+            path     : path.as_ref().to_path_buf(),
+            capacity,
+            state    : Mutex::new(StoreState { map, next_seq: 1, next_slot: 0, dropped: 0 })
+        }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn register_frame(&self, id: FrameId, name: &str) -> Result<(), TfError> {
+        if id == 0 || id as usize > MAX_FRAMES {
+            return Err(TfError::InvalidArgument(format!("disk stores hold at most {} frames", MAX_FRAMES)));
+        }
+        if name.len() > MAX_NAME_LENGTH {
+            return Err(TfError::InvalidArgument(format!("frame name {} is too long for a disk store", name)));
+        }
+        let mut state = self.state.lock().unwrap();
+        let entry = &mut state.map[HEADER_SIZE + (id as usize - 1) * NAME_SIZE..][..NAME_SIZE];
+        put(entry, 4, &(name.len() as u32).to_le_bytes());
+        put(entry, 8, name.as_bytes());
+        // the id marks the entry as complete
+        put(entry, 0, &id.to_le_bytes());
+        Ok(())
+    }
+
+    // Samples of frames that were not registered, e.g. past MAX_FRAMES,
+    // could not be read back and are only counted
+    pub fn append(&self, ts: &TransformStorage, is_static: bool) {
+        let mut state = self.state.lock().unwrap();
+        if !is_registered(&state.map, ts.frame_id) || !is_registered(&state.map, ts.child_frame_id) {
+            state.dropped += 1;
+            return;
+        }
+        if is_static {
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            let offset = statics_offset() + (ts.child_frame_id as usize - 1) * RECORD_SIZE;
+            encode_record(&mut state.map[offset..offset + RECORD_SIZE], seq, ts, STATIC_FLAG);
+        } else {
+            self.write_record(&mut state, ts, 0);
+        }
+    }
+
+    pub fn append_reset(&self) {
+        let ts = TransformStorage {
+            frame_id       : 0,
+            child_frame_id : 0,
+            translation    : NTranslation3::identity(),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(0)
+        };
+        self.write_record(&mut self.state.lock().unwrap(), &ts, RESET_FLAG);
+    }
+
+    fn write_record(&self, state: &mut StoreState, ts: &TransformStorage, flags: u32) {
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let offset = records_offset() + state.next_slot * RECORD_SIZE;
+        state.next_slot = (state.next_slot + 1) % self.capacity;
+        encode_record(&mut state.map[offset..offset + RECORD_SIZE], seq, ts, flags);
+    }
+
+    pub fn dropped_records(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+
+    pub fn flush(&self) -> Result<(), TfError> {
+        Ok(self.state.lock().unwrap().map.flush()?)
+    }
+
+    // Records every frame of a buffer from now on. It must be attached
+    // before the first transform, the caches of existing frames would
+    // not write to the store. Resets of the buffer on time jumps are
+    // recorded, Buffer::clear is not: the file keeps its history.
+    pub fn attach(self: &Arc<Self>, buffer: &mut Buffer) -> Result<(), TfError> {
+        if !buffer.get_frame_names().is_empty() {
+            return Err(TfError::InvalidArgument("a disk store must be attached to an empty buffer".to_string()));
+        }
+        let store = self.clone();
+        buffer.set_cache_factory(Some(Arc::new(move ||
+            Box::new(DiskCache::new(store.clone(), Box::new(TimeCache::new()), false)) as Box<dyn TimeCacheInterface>)));
+        let store = self.clone();
+        buffer.set_static_cache_factory(Some(Arc::new(move ||
+            Box::new(DiskCache::new(store.clone(), Box::new(StaticCache::new()), true)) as Box<dyn TimeCacheInterface>)));
+        let store = self.clone();
+        buffer.add_frame_callback(Box::new(move |id, name| {
+            // the samples of frames that do not fit are counted by append
+            let _ = store.register_frame(id, name);
+        }));
+        let store = self.clone();
+        buffer.add_reset_callback(Box::new(move |_| store.append_reset()));
+        Ok(())
+    }
+
+}
+
+// Keeps lookups in memory and appends every accepted sample to the store
+pub struct DiskCache {
+    inner     : Box<dyn TimeCacheInterface>,
+    store     : Arc<DiskStore>,
+    is_static : bool
+}
+
+impl DiskCache {
+
+    pub fn new(store: Arc<DiskStore>, inner: Box<dyn TimeCacheInterface>, is_static: bool) -> DiskCache {
+        DiskCache {
+            inner,
+            store,
+            is_static
+        }
+    }
+
+}
+
+impl TimeCacheInterface for DiskCache {
+
+    fn get_data(&self, stamp: &Stamp) -> Result<TransformStorage, TfError> {
+        self.inner.get_data(stamp)
+    }
+
+    fn get_parent(&self, stamp: &Stamp) -> Result<FrameId, TfError> {
+        self.inner.get_parent(stamp)
+    }
+
+    fn insert_data(&mut self, new_ts: TransformStorage) -> bool {
+        if !self.inner.insert_data(new_ts.clone()) {
+            return false;
+        }
+        self.store.append(&new_ts, self.is_static);
+        true
+    }
+
+    // the file keeps its history, see DiskStore::attach
+    fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    fn get_latest_time_and_parent(&self) -> Option<(Stamp, FrameId)> {
+        self.inner.get_latest_time_and_parent()
+    }
+
+    fn get_length(&self) -> usize {
+        self.inner.get_length()
+    }
+
+    fn get_latest_timestamp(&self) -> Option<Stamp> {
+        self.inner.get_latest_timestamp()
+    }
+
+    fn get_oldest_timestamp(&self) -> Option<Stamp> {
+        self.inner.get_oldest_timestamp()
+    }
+
+    fn get_all_data(&self) -> Vec<TransformStorage> {
+        self.inner.get_all_data()
+    }
+
+    fn prune_older_than(&mut self, stamp: &Stamp) {
+        self.inner.prune_older_than(stamp);
+    }
+
+    fn clone_cache(&self) -> Box<dyn TimeCacheInterface> {
+        Box::new(DiskCache::new(self.store.clone(), self.inner.clone_cache(), self.is_static))
+    }
+
+}
+
+// The samples of a store in the order they were written, with whether
+// they are static
+pub fn read_disk_store_transforms<P: AsRef<Path>>(path: P) -> Result<Vec<(Transform, bool)>, TfError> {
+    let data = std::fs::read(path)?;
+    if data.len() < HEADER_SIZE || &data[..8] != MAGIC {
+        return Err(decode_error("not a disk store"));
+    }
+    let capacity = get_u64(&data, 8) as usize;
+    if data.len() < file_size(capacity) {
+        return Err(decode_error("file is truncated"));
+    }
+
+    let mut names = HashMap::new();
+    for entry in data[HEADER_SIZE..records_offset()].chunks(NAME_SIZE) {
+        let id = get_u32(entry, 0);
+        let len = get_u32(entry, 4) as usize;
+        if id == 0 || len > MAX_NAME_LENGTH {
+            continue;
+        }
+        if let Ok(name) = String::from_utf8(entry[8..8 + len].to_vec()) {
+            names.insert(id, name);
+        }
+    }
+
+    let mut records: Vec<_> = data[statics_offset()..file_size(capacity)].chunks(RECORD_SIZE)
+        .filter_map(decode_record).collect();
+    records.sort_by_key(|(seq, _, _)| *seq);
+    // static samples survive a reset, like in the buffer
+    let last_reset = records.iter().filter(|x| x.2 & RESET_FLAG != 0).map(|x| x.0).max().unwrap_or(0);
+    Ok(records.into_iter().filter_map(|(seq, ts, flags)| {
+        let is_static = flags & STATIC_FLAG != 0;
+        if flags & RESET_FLAG != 0 || (seq < last_reset && !is_static) {
+            return None;
+        }
+        Some((Transform {
+            frame_id       : names.get(&ts.frame_id)?.clone(),
+            child_frame_id : names.get(&ts.child_frame_id)?.clone(),
+            translation    : ts.translation,
+            rotation       : ts.rotation,
+            stamp          : ts.stamp
+        }, is_static))
+    }).collect())
+}
+
+// Loads what a store holds into a buffer, e.g. after a crash. Returns
+// the number of transforms read.
+pub fn read_disk_store<P: AsRef<Path>>(path: P, buffer: &mut Buffer) -> Result<usize, TfError> {
+    let transforms = read_disk_store_transforms(path)?;
+    for (transform, is_static) in transforms.iter() {
+        buffer.set_transform(transform, *is_static)?;
+    }
+    Ok(transforms.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tf_disk_store_{}_{}", std::process::id(), name))
+    }
+
+    fn make_transform(parent: &str, child: &str, x: f64, nanos: i64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    #[test]
+    fn test_recover_buffer() {
+        let path = temp_path("recover");
+        let store = DiskStore::create(&path, 100).unwrap();
+        let mut buffer = Buffer::new();
+        store.attach(&mut buffer).unwrap();
+        buffer.set_transform(&make_transform("map", "odom", 1.0, 0), true).unwrap();
+        for i in 0..10 {
+            buffer.set_transform(&make_transform("odom", "base_link", i as f64, i * 100), false).unwrap();
+        }
+
+        // read while the store is still alive, as after a crash
+        let mut recovered = Buffer::new();
+        assert_eq!(11, read_disk_store(&path, &mut recovered).unwrap());
+        let frame = recovered.get_frame_number("odom").unwrap();
+        assert!(recovered.is_static(frame));
+        let res = recovered.lookup_transform("map", "base_link", &Stamp::from_nanos(450)).unwrap();
+        assert!(abs_diff_eq!(5.5, res.translation.vector.x));
+
+        let mut late = Buffer::new();
+        late.set_transform(&make_transform("map", "odom", 1.0, 0), true).unwrap();
+        assert!(store.attach(&mut late).is_err());
+        assert!(store.register_frame(1, &"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_overwrite_and_torn_records() {
+        let path = temp_path("overwrite");
+        let store = DiskStore::create(&path, 4).unwrap();
+        let mut buffer = Buffer::new();
+        store.attach(&mut buffer).unwrap();
+        for i in 0..6 {
+            buffer.set_transform(&make_transform("odom", "base_link", i as f64, i * 100), false).unwrap();
+        }
+        store.flush().unwrap();
+        drop(buffer);
+        drop(store);
+
+        let transforms = read_disk_store_transforms(&path).unwrap();
+        let xs: Vec<f64> = transforms.iter().map(|(t, _)| t.translation.vector.x).collect();
+        assert_eq!(vec![2.0, 3.0, 4.0, 5.0], xs);
+
+        // the newest sample is in slot 1
+        let mut data = std::fs::read(&path).unwrap();
+        data[records_offset() + RECORD_SIZE + 30] ^= 0xff;
+        std::fs::write(&path, &data).unwrap();
+        let transforms = read_disk_store_transforms(&path).unwrap();
+        assert_eq!(3, transforms.len());
+        assert!(abs_diff_eq!(4.0, transforms[2].0.translation.vector.x));
+
+        std::fs::write(&path, b"not a store").unwrap();
+        assert!(read_disk_store_transforms(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reset_and_dropped_records() {
+        let path = temp_path("reset");
+        let store = DiskStore::create(&path, 100).unwrap();
+        let mut buffer = Buffer::new();
+        store.attach(&mut buffer).unwrap();
+        buffer.set_time_jump_threshold(Some(Stamp::from_nanos(1_000_000_000)));
+        buffer.set_transform(&make_transform("base_link", "laser", 0.5, 0), true).unwrap();
+        buffer.set_transform(&make_transform("odom", "base_link", 5.0, 5_000_000_000), false).unwrap();
+        buffer.set_transform(&make_transform("odom", "base_link", 6.0, 6_000_000_000), false).unwrap();
+        // the bag looped
+        buffer.set_transform(&make_transform("odom", "base_link", 1.0, 1_000_000_000), false).unwrap();
+
+        let long_name = "a".repeat(MAX_NAME_LENGTH + 1);
+        buffer.set_transform(&make_transform("odom", &long_name, 1.0, 1_000_000_000), false).unwrap();
+        assert_eq!(1, store.dropped_records());
+
+        let transforms = read_disk_store_transforms(&path).unwrap();
+        let stamps: Vec<(i64, bool)> = transforms.iter().map(|(t, is_static)| (t.stamp.nanos(), *is_static)).collect();
+        assert_eq!(vec![(0, true), (1_000_000_000, false)], stamps);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_static_survives_overwrite() {
+        let path = temp_path("static");
+        let store = DiskStore::create(&path, 4).unwrap();
+        let mut buffer = Buffer::new();
+        store.attach(&mut buffer).unwrap();
+        buffer.set_transform(&make_transform("base_link", "laser", 0.5, 0), true).unwrap();
+        for i in 0..10 {
+            buffer.set_transform(&make_transform("odom", "base_link", i as f64, i * 100), false).unwrap();
+            if i == 8 {
+                buffer.set_transform(&make_transform("base_link", "imu", 0.1, 0), true).unwrap();
+            }
+        }
+
+        // the four newest dynamic samples and both static ones
+        let mut recovered = Buffer::new();
+        assert_eq!(6, read_disk_store(&path, &mut recovered).unwrap());
+        let base_link = recovered.get_frame_number("base_link").unwrap();
+        assert_eq!(4, recovered.get_cache(base_link).unwrap().get_length());
+        let res = recovered.lookup_transform("odom", "laser", &Stamp::from_nanos(850)).unwrap();
+        assert!(abs_diff_eq!(9.0, res.translation.vector.x));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+}
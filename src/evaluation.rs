@@ -0,0 +1,345 @@
+use super::transform_storage::{Stamp, NVector3, NQuaternion, NTranslation3};
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{Buffer, Transform};
+use super::trajectory::sample_trajectory;
+
+use nalgebra::{Isometry3, Matrix3, Rotation3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    None,
+    // rotation and translation
+    Se3,
+    // rotation, translation and scale, e.g. for monocular SLAM
+    Sim3
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorStatistics {
+    pub count      : usize,
+    pub rmse       : f64,
+    pub mean       : f64,
+    pub median     : f64,
+    pub std        : f64,
+    pub min        : f64,
+    pub max        : f64,
+    // NaN or infinite errors, left out of the values above
+    pub non_finite : usize
+}
+
+impl ErrorStatistics {
+
+    // all zeros without finite errors
+    pub fn from_errors(errors: &[f64]) -> ErrorStatistics {
+        let mut sorted: Vec<f64> = errors.iter().cloned().filter(|x| x.is_finite()).collect();
+        let non_finite = errors.len() - sorted.len();
+        if sorted.is_empty() {
+            return ErrorStatistics { count: 0, rmse: 0.0, mean: 0.0, median: 0.0, std: 0.0, min: 0.0, max: 0.0, non_finite };
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let errors = &sorted;
+        let count = errors.len();
+        let n = count as f64;
+        let mean = errors.iter().sum::<f64>() / n;
+        let median = if count % 2 == 0 {
+            (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
+        } else {
+            sorted[count / 2]
+        };
+        ErrorStatistics {
+            count,
+            rmse   : (errors.iter().map(|x| x * x).sum::<f64>() / n).sqrt(),
+            mean,
+            median,
+            std    : (errors.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / n).sqrt(),
+            min    : sorted[0],
+            max    : sorted[count - 1],
+            non_finite
+        }
+    }
+
+}
+
+// x -> scale * rotation * x + translation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Similarity {
+    pub rotation    : NQuaternion,
+    pub translation : NVector3,
+    pub scale       : f64
+}
+
+impl Similarity {
+
+    pub fn identity() -> Similarity {
+        Similarity {
+            rotation    : NQuaternion::identity(),
+            translation : NVector3::zeros(),
+            scale       : 1.0
+        }
+    }
+
+    pub fn apply(&self, point: &NVector3) -> NVector3 {
+        self.rotation * point * self.scale + self.translation
+    }
+
+    // the orientation is only rotated, the scale applies to the position
+    pub fn apply_to_pose(&self, pose: &Isometry3<f64>) -> Isometry3<f64> {
+        Isometry3::from_parts(NTranslation3::from(self.apply(&pose.translation.vector)), self.rotation * pose.rotation)
+    }
+
+}
+
+// Least squares similarity taking `source` onto `target` (Umeyama, 1991),
+// with a scale of one unless `with_scale`
+pub fn umeyama(source: &[NVector3], target: &[NVector3], with_scale: bool) -> Result<Similarity, TfError> {
+    if source.len() != target.len() {
+        return Err(TfError::InvalidArgument("umeyama needs as many source as target points".to_string()));
+    }
+    if source.len() < 3 {
+        return Err(TfError::InvalidArgument("umeyama needs at least three points".to_string()));
+    }
+    let n = source.len() as f64;
+    let source_mean = source.iter().fold(NVector3::zeros(), |sum, x| sum + x) / n;
+    let target_mean = target.iter().fold(NVector3::zeros(), |sum, x| sum + x) / n;
+    let mut covariance = Matrix3::zeros();
+    let mut source_variance = 0.0;
+    for (s, t) in source.iter().zip(target.iter()) {
+        let s = s - source_mean;
+        covariance += (t - target_mean) * s.transpose();
+        source_variance += s.norm_squared();
+    }
+    covariance /= n;
+    source_variance /= n;
+
+    let svd = covariance.svd(true, true);
+    let (u, v_t) = (svd.u.unwrap(), svd.v_t.unwrap());
+    // the singular values are not sorted, a reflection flips the axis of
+    // the smallest one
+    let mut signs = Matrix3::identity();
+    if u.determinant() * v_t.determinant() < 0.0 {
+        let smallest = svd.singular_values.iamin();
+        signs[(smallest, smallest)] = -1.0;
+    }
+    let rotation = u * signs * v_t;
+    let scale = if with_scale {
+        if source_variance < std::f64::EPSILON {
+            return Err(TfError::InvalidArgument("umeyama needs source points that are not all the same".to_string()));
+        }
+        (Matrix3::from_diagonal(&svd.singular_values) * signs).trace() / source_variance
+    } else {
+        1.0
+    };
+    let rotation = NQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(rotation));
+    Ok(Similarity {
+        translation : target_mean - rotation * source_mean * scale,
+        rotation,
+        scale
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct EvaluationOptions {
+    pub alignment : Alignment,
+    // None compares at the stamps of the estimate
+    pub period    : Option<Stamp>,
+    pub start     : Stamp,
+    pub end       : Stamp,
+    // RPE compares the motion between poses this many pairs apart
+    pub rpe_delta : usize
+}
+
+impl Default for EvaluationOptions {
+    fn default() -> EvaluationOptions {
+        EvaluationOptions {
+            alignment : Alignment::Se3,
+            period    : None,
+            start     : Stamp::from_nanos(0),
+            end       : Stamp::from_nanos(i64::max_value()),
+            rpe_delta : 1
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub pairs           : usize,
+    // applied to the estimate before computing the errors
+    pub alignment       : Similarity,
+    // position error, in the units of the reference
+    pub ate             : ErrorStatistics,
+    pub rpe_translation : ErrorStatistics,
+    // radians
+    pub rpe_rotation    : ErrorStatistics
+}
+
+fn to_isometry(transform: &Transform) -> Isometry3<f64> {
+    Isometry3::from_parts(transform.translation, transform.rotation)
+}
+
+// Pairs each pose of `parent -> child` in `estimate` with the pose of
+// `reference` interpolated at the same time, then computes ATE and RPE.
+pub fn evaluate(buffer: &Buffer, estimate: (&str, &str), reference: (&str, &str),
+                options: &EvaluationOptions) -> Result<Evaluation, TfError> {
+    let estimated = sample_trajectory(buffer, estimate.0, estimate.1, &options.start, &options.end, options.period.as_ref())?;
+    let mut pairs = Vec::new();
+    for transform in estimated.iter() {
+        match buffer.lookup_transform(reference.0, reference.1, &transform.stamp) {
+            Ok(reference) => pairs.push((to_isometry(transform), to_isometry(&reference))),
+            Err(TfError::ExtrapolationError1) | Err(TfError::ExtrapolationError2) | Err(TfError::ExtrapolationError3) |
+            Err(TfError::ExtrapolationError(_)) => {},
+            Err(err) => return Err(err)
+        }
+    }
+    if pairs.is_empty() {
+        return Err(TfError::LookupError("the estimate and the reference do not overlap in time".to_string()));
+    }
+
+    let source: Vec<NVector3> = pairs.iter().map(|(e, _)| e.translation.vector).collect();
+    let target: Vec<NVector3> = pairs.iter().map(|(_, r)| r.translation.vector).collect();
+    let alignment = match options.alignment {
+        Alignment::None => Similarity::identity(),
+        Alignment::Se3 => umeyama(&source, &target, false)?,
+        Alignment::Sim3 => umeyama(&source, &target, true)?
+    };
+    let aligned: Vec<(Isometry3<f64>, &Isometry3<f64>)> = pairs.iter()
+        .map(|(e, r)| (alignment.apply_to_pose(e), r))
+        .collect();
+
+    let ate: Vec<f64> = aligned.iter()
+        .map(|(e, r)| (e.translation.vector - r.translation.vector).norm())
+        .collect();
+    let mut rpe_translation = Vec::new();
+    let mut rpe_rotation = Vec::new();
+    if options.rpe_delta > 0 {
+        for (first, second) in aligned.iter().zip(aligned.iter().skip(options.rpe_delta)) {
+            let estimate_motion = first.0.inverse() * second.0;
+            let reference_motion = first.1.inverse() * second.1;
+            let error = reference_motion.inverse() * estimate_motion;
+            rpe_translation.push(error.translation.vector.norm());
+            rpe_rotation.push(error.rotation.angle());
+        }
+    }
+
+    Ok(Evaluation {
+        pairs           : pairs.len(),
+        alignment,
+        ate             : ErrorStatistics::from_errors(&ate),
+        rpe_translation : ErrorStatistics::from_errors(&rpe_translation),
+        rpe_rotation    : ErrorStatistics::from_errors(&rpe_rotation)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_transform(parent: &str, child: &str, pose: &Isometry3<f64>, nanos: i64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : pose.translation,
+            rotation       : pose.rotation,
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    fn ground_truth(i: usize) -> Isometry3<f64> {
+        let i = i as f64;
+        Isometry3::from_parts(NTranslation3::new(i.cos() * 3.0, i.sin() * 3.0, 0.2 * i),
+                              NQuaternion::from_euler_angles(0.0, 0.1 * i, 0.3 * i))
+    }
+
+    // the estimate lives in another frame, with positions scaled by `scale`
+    fn make_buffer(scale: f64) -> Buffer {
+        let offset = Isometry3::from_parts(NTranslation3::new(1.0, -2.0, 0.5), NQuaternion::from_euler_angles(0.1, 0.2, 1.0));
+        let mut buffer = Buffer::new();
+        for i in 0..20 {
+            let stamp = (i as i64 + 1) * 100_000_000;
+            let gt = ground_truth(i);
+            buffer.set_transform(&make_transform("world", "gt_base", &gt, stamp), false).unwrap();
+            let mut estimate = offset * gt;
+            estimate.translation.vector *= scale;
+            // a little later, so that the reference is interpolated
+            buffer.set_transform(&make_transform("map", "base_link", &estimate, stamp + 10), false).unwrap();
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_statistics() {
+        let stats = ErrorStatistics::from_errors(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(4, stats.count);
+        assert!(abs_diff_eq!(7.5f64.sqrt(), stats.rmse));
+        assert!(abs_diff_eq!(2.5, stats.mean));
+        assert!(abs_diff_eq!(2.5, stats.median));
+        assert!(abs_diff_eq!(1.25f64.sqrt(), stats.std));
+        assert!(abs_diff_eq!(1.0, stats.min));
+        assert!(abs_diff_eq!(4.0, stats.max));
+        assert_eq!(0, ErrorStatistics::from_errors(&[]).count);
+
+        let stats = ErrorStatistics::from_errors(&[2.0, std::f64::NAN, 1.0, std::f64::INFINITY]);
+        assert_eq!(2, stats.count);
+        assert_eq!(2, stats.non_finite);
+        assert!(abs_diff_eq!(1.5, stats.median));
+        assert!(abs_diff_eq!(2.0, stats.max));
+    }
+
+    #[test]
+    fn test_umeyama() {
+        let expected = Similarity {
+            rotation    : NQuaternion::from_euler_angles(0.3, -0.2, 2.0),
+            translation : NVector3::new(1.0, 2.0, 3.0),
+            scale       : 2.5
+        };
+        let source: Vec<NVector3> = (0..10).map(|i| ground_truth(i).translation.vector).collect();
+        let target: Vec<NVector3> = source.iter().map(|x| expected.apply(x)).collect();
+        let res = umeyama(&source, &target, true).unwrap();
+        assert!(abs_diff_eq!(2.5, res.scale, epsilon = 1.0e-9));
+        assert!(res.rotation.angle_to(&expected.rotation) < 1.0e-9);
+        assert!((expected.translation - res.translation).norm() < 1.0e-9);
+        assert!(umeyama(&source[..2], &target[..2], false).is_err());
+
+        // planar points, one singular value is zero
+        let source: Vec<NVector3> = [(0.0, 0.0), (4.0, 0.0), (0.0, 1.0), (3.0, 2.0), (-1.0, 5.0)].iter()
+            .map(|&(x, y)| NVector3::new(x, y, 0.0)).collect();
+        let target: Vec<NVector3> = source.iter().map(|x| expected.apply(x)).collect();
+        let res = umeyama(&source, &target, true).unwrap();
+        assert!(abs_diff_eq!(2.5, res.scale, epsilon = 1.0e-9));
+        assert!(res.rotation.angle_to(&expected.rotation) < 1.0e-9);
+    }
+
+    #[test]
+    fn test_evaluate_se3() {
+        let buffer = make_buffer(1.0);
+        let aligned = evaluate(&buffer, ("map", "base_link"), ("world", "gt_base"), &EvaluationOptions::default()).unwrap();
+        // the first estimate is before the first reference
+        assert_eq!(19, aligned.pairs);
+        assert!(aligned.ate.rmse < 1.0e-6);
+        assert!(aligned.rpe_translation.max < 1.0e-6);
+        assert!(aligned.rpe_rotation.max < 1.0e-6);
+        assert_eq!(18, aligned.rpe_translation.count);
+
+        let options = EvaluationOptions { alignment: Alignment::None, rpe_delta: 2, ..EvaluationOptions::default() };
+        let unaligned = evaluate(&buffer, ("map", "base_link"), ("world", "gt_base"), &options).unwrap();
+        assert!(unaligned.ate.rmse > 1.0);
+        // relative motion does not depend on where the trajectory starts
+        assert!(unaligned.rpe_translation.max < 1.0e-6);
+        assert_eq!(17, unaligned.rpe_rotation.count);
+    }
+
+    #[test]
+    fn test_evaluate_sim3() {
+        let buffer = make_buffer(0.5);
+        let options = EvaluationOptions { alignment: Alignment::Sim3, ..EvaluationOptions::default() };
+        let res = evaluate(&buffer, ("map", "base_link"), ("world", "gt_base"), &options).unwrap();
+        assert!(abs_diff_eq!(2.0, res.alignment.scale, epsilon = 1.0e-6));
+        assert!(res.ate.rmse < 1.0e-6);
+        assert!(res.rpe_translation.max < 1.0e-6);
+
+        let se3 = evaluate(&buffer, ("map", "base_link"), ("world", "gt_base"), &EvaluationOptions::default()).unwrap();
+        assert!(se3.ate.rmse > 0.1);
+
+        assert!(evaluate(&buffer, ("map", "base_link"), ("world", "laser"), &options).is_err());
+    }
+
+}
@@ -0,0 +1,237 @@
+use super::transform_storage::{Stamp, NTranslation3, NQuaternion};
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{Buffer, Transform};
+
+use nalgebra::Quaternion;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, RwLock};
+
+// Error codes of the C API, the same categories as tf2_msgs/TF2Error.
+// The doc comments end up in include/rosrust_tf.h, regenerate it with
+// cbindgen after changing them.
+/// Success.
+pub const TF_OK: c_int = 0;
+/// A frame does not exist.
+pub const TF_LOOKUP_ERROR: c_int = 1;
+/// The frames are in trees that are not connected.
+pub const TF_CONNECTIVITY_ERROR: c_int = 2;
+/// A link has no data at the requested time.
+pub const TF_EXTRAPOLATION_ERROR: c_int = 3;
+/// A null pointer, an invalid frame id or an invalid transform.
+pub const TF_INVALID_ARGUMENT_ERROR: c_int = 4;
+/// Any other failure, including panics, which never reach C.
+pub const TF_OTHER_ERROR: c_int = 5;
+
+/// A transform buffer, opaque to C.
+///
+/// Every function but tf_buffer_free may be called on the same handle from
+/// several threads at once. Pointers passed to the API must be null or
+/// valid, and frame ids nul-terminated UTF-8. Null pointers are reported as
+/// TF_INVALID_ARGUMENT_ERROR.
+pub struct TfBuffer {
+    buffer : Arc<RwLock<Buffer>>
+}
+
+/// A transform and its stamp in nanoseconds. Rotations are quaternions,
+/// they are normalized when set.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TfTransform {
+    pub translation_x : f64,
+    pub translation_y : f64,
+    pub translation_z : f64,
+    pub rotation_x    : f64,
+    pub rotation_y    : f64,
+    pub rotation_z    : f64,
+    pub rotation_w    : f64,
+    pub stamp_nanos   : i64
+}
+
+impl TfTransform {
+    fn from_transform(transform: &Transform) -> TfTransform {
+        let t = &transform.translation.vector;
+        let q = &transform.rotation.coords;
+        TfTransform {
+            translation_x : t.x,
+            translation_y : t.y,
+            translation_z : t.z,
+            rotation_x    : q.x,
+            rotation_y    : q.y,
+            rotation_z    : q.z,
+            rotation_w    : q.w,
+            stamp_nanos   : transform.stamp.nanos()
+        }
+    }
+}
+
+fn error_code(err: &TfError) -> c_int {
+    match err {
+        TfError::LookupError(_) | TfError::TransformNotFound | TfError::NoParent => TF_LOOKUP_ERROR,
+        TfError::ConnectivityError(_) => TF_CONNECTIVITY_ERROR,
+        TfError::ExtrapolationError1 | TfError::ExtrapolationError2 | TfError::ExtrapolationError3 |
+        TfError::ExtrapolationError(_) => TF_EXTRAPOLATION_ERROR,
+        TfError::InvalidArgument(_) => TF_INVALID_ARGUMENT_ERROR,
+        _ => TF_OTHER_ERROR
+    }
+}
+
+unsafe fn to_str<'a>(name: *const c_char) -> Result<&'a str, TfError> {
+    if name.is_null() {
+        return Err(TfError::InvalidArgument("null frame id".to_string()));
+    }
+    CStr::from_ptr(name).to_str().map_err(|_| TfError::InvalidArgument("frame id is not valid utf-8".to_string()))
+}
+
+unsafe fn to_buffer<'a>(handle: *const TfBuffer) -> Result<&'a TfBuffer, TfError> {
+    handle.as_ref().ok_or_else(|| TfError::InvalidArgument("null buffer".to_string()))
+}
+
+// panics must not unwind into C
+fn guard<F: FnOnce() -> Result<(), TfError>>(f: F) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => TF_OK,
+        Ok(Err(err)) => error_code(&err),
+        Err(_) => TF_OTHER_ERROR
+    }
+}
+
+// Hands a buffer the Rust side keeps updating, e.g. the one of a
+// TransformListener, to C code. Free it with tf_buffer_free.
+pub fn tf_buffer_from_shared(buffer: Arc<RwLock<Buffer>>) -> *mut TfBuffer {
+    Box::into_raw(Box::new(TfBuffer { buffer }))
+}
+
+/// Creates an empty buffer, owned by the caller until tf_buffer_free.
+#[no_mangle]
+pub extern "C" fn tf_buffer_new() -> *mut TfBuffer {
+    tf_buffer_from_shared(Arc::new(RwLock::new(Buffer::new())))
+}
+
+/// Frees a buffer, null is ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or come from tf_buffer_new, and must not be used
+/// by any thread during or after this call.
+#[no_mangle]
+pub unsafe extern "C" fn tf_buffer_free(handle: *mut TfBuffer) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Inserts the transform from `parent_frame` to `child_frame`. The strings
+/// and `transform` are copied, the caller keeps them. Returns
+/// TF_INVALID_ARGUMENT_ERROR for invalid frame ids and for rotations that
+/// are zero or not finite.
+///
+/// # Safety
+///
+/// `handle` must be null or a live buffer, the frame ids null or
+/// nul-terminated strings and `transform` null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn tf_buffer_set_transform(handle: *const TfBuffer, parent_frame: *const c_char,
+                                                 child_frame: *const c_char, transform: *const TfTransform,
+                                                 is_static: c_int) -> c_int {
+    guard(|| {
+        let handle = to_buffer(handle)?;
+        let t = transform.as_ref().ok_or_else(|| TfError::InvalidArgument("null transform".to_string()))?;
+        // normalizing a zero quaternion gives NaN
+        let rotation = Quaternion::new(t.rotation_w, t.rotation_x, t.rotation_y, t.rotation_z);
+        if !rotation.norm().is_finite() || rotation.norm() == 0.0 {
+            return Err(TfError::InvalidArgument("the rotation is not a valid quaternion".to_string()));
+        }
+        let transform = Transform {
+            frame_id       : to_str(parent_frame)?.to_string(),
+            child_frame_id : to_str(child_frame)?.to_string(),
+            translation    : NTranslation3::new(t.translation_x, t.translation_y, t.translation_z),
+            rotation       : NQuaternion::from_quaternion(rotation),
+            stamp          : Stamp::from_nanos(t.stamp_nanos)
+        };
+        handle.buffer.write().unwrap().set_transform(&transform, is_static != 0)
+    })
+}
+
+/// Looks up the transform that maps `source_frame` coordinates into
+/// `target_frame`. A time of zero asks for the latest transform. `result`
+/// is only written on success.
+///
+/// # Safety
+///
+/// `handle` must be null or a live buffer, the frame ids null or
+/// nul-terminated strings and `result` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tf_buffer_lookup_transform(handle: *const TfBuffer, target_frame: *const c_char,
+                                                    source_frame: *const c_char, stamp_nanos: i64,
+                                                    result: *mut TfTransform) -> c_int {
+    guard(|| {
+        let handle = to_buffer(handle)?;
+        let result = result.as_mut().ok_or_else(|| TfError::InvalidArgument("null result".to_string()))?;
+        let transform = handle.buffer.read().unwrap()
+            .lookup_transform(to_str(target_frame)?, to_str(source_frame)?, &Stamp::from_nanos(stamp_nanos))?;
+        *result = TfTransform::from_transform(&transform);
+        Ok(())
+    })
+}
+
+/// 1 if tf_buffer_lookup_transform would succeed, 0 otherwise.
+///
+/// # Safety
+///
+/// `handle` must be null or a live buffer and the frame ids null or
+/// nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn tf_buffer_can_transform(handle: *const TfBuffer, target_frame: *const c_char,
+                                                 source_frame: *const c_char, stamp_nanos: i64) -> c_int {
+    let mut result = TfTransform::default();
+    (tf_buffer_lookup_transform(handle, target_frame, source_frame, stamp_nanos, &mut result) == TF_OK) as c_int
+}
+
+/// A description of an error code. The string is static, do not free it.
+#[no_mangle]
+pub extern "C" fn tf_error_string(code: c_int) -> *const c_char {
+    let description: &'static [u8] = match code {
+        TF_OK => b"no error\0",
+        TF_LOOKUP_ERROR => b"lookup error\0",
+        TF_CONNECTIVITY_ERROR => b"connectivity error\0",
+        TF_EXTRAPOLATION_ERROR => b"extrapolation error\0",
+        TF_INVALID_ARGUMENT_ERROR => b"invalid argument\0",
+        _ => b"transform error\0"
+    };
+    description.as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_shared_buffer() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let handle = tf_buffer_from_shared(buffer.clone());
+        buffer.write().unwrap().set_transform(&Transform {
+            frame_id       : "map".to_string(),
+            child_frame_id : "odom".to_string(),
+            translation    : NTranslation3::new(1.0, 2.0, 3.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(0)
+        }, true).unwrap();
+
+        let (map, odom) = (CString::new("map").unwrap(), CString::new("odom").unwrap());
+        let mut result = TfTransform::default();
+        unsafe {
+            assert_eq!(TF_OK, tf_buffer_lookup_transform(handle, map.as_ptr(), odom.as_ptr(), 0, &mut result));
+            assert_eq!(TF_INVALID_ARGUMENT_ERROR, tf_buffer_lookup_transform(handle, std::ptr::null(), odom.as_ptr(), 0, &mut result));
+            let zero = TfTransform::default();
+            assert_eq!(TF_INVALID_ARGUMENT_ERROR, tf_buffer_set_transform(handle, map.as_ptr(), odom.as_ptr(), &zero, 0));
+            tf_buffer_free(handle);
+        }
+        assert!(abs_diff_eq!(2.0, result.translation_y));
+        assert!(abs_diff_eq!(1.0, result.rotation_w));
+    }
+
+}
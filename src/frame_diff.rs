@@ -0,0 +1,221 @@
+use super::transform_storage::{Stamp, NTranslation3, NQuaternion};
+use super::tf_buffer::tf::Buffer;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+pub struct DiffTolerance {
+    // meters
+    pub translation : f64,
+    // radians
+    pub rotation    : f64
+}
+
+impl Default for DiffTolerance {
+    fn default() -> DiffTolerance {
+        DiffTolerance {
+            translation : 1.0e-6,
+            rotation    : 1.0e-6
+        }
+    }
+}
+
+// Parents are None for the roots of the tree
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum FrameChange {
+    Added {
+        frame  : String,
+        parent : Option<String>
+    },
+    Removed {
+        frame  : String,
+        parent : Option<String>
+    },
+    Reparented {
+        frame      : String,
+        old_parent : Option<String>,
+        new_parent : Option<String>
+    },
+    // a static transform that moved relative to its unchanged parent
+    Moved {
+        frame       : String,
+        parent      : String,
+        translation : f64,
+        rotation    : f64
+    }
+}
+
+fn parent_name(parent: &Option<String>) -> &str {
+    parent.as_ref().map(|x| x.as_str()).unwrap_or("no parent")
+}
+
+impl fmt::Display for FrameChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameChange::Added { frame, parent } =>
+                write!(f, "+ {} ({})", frame, parent_name(parent)),
+            FrameChange::Removed { frame, parent } =>
+                write!(f, "- {} ({})", frame, parent_name(parent)),
+            FrameChange::Reparented { frame, old_parent, new_parent } =>
+                write!(f, "~ {}: parent {} -> {}", frame, parent_name(old_parent), parent_name(new_parent)),
+            FrameChange::Moved { frame, parent, translation, rotation } =>
+                write!(f, "* {}: moved {:.6} m and {:.3} deg relative to {}",
+                       frame, translation, rotation.to_degrees(), parent)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FrameDiff {
+    // sorted by frame
+    pub changes : Vec<FrameChange>
+}
+
+impl FrameDiff {
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+}
+
+impl fmt::Display for FrameDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+struct FrameState {
+    parent    : Option<String>,
+    // only for static frames
+    transform : Option<(NTranslation3, NQuaternion)>
+}
+
+// Frames that cannot answer at `time` keep their latest parent
+fn frame_states(buffer: &Buffer, time: &Stamp) -> BTreeMap<String, FrameState> {
+    let mut states = BTreeMap::new();
+    for name in buffer.get_frame_names() {
+        let id = buffer.get_frame_number(&name).unwrap();
+        let cache = buffer.get_cache(id);
+        let parent = cache.and_then(|cache| cache.get_parent(time).ok()
+                                        .or_else(|| cache.get_latest_time_and_parent().map(|x| x.1)));
+        let transform = match cache {
+            Some(cache) if buffer.is_static(id) => cache.get_data(time).ok().map(|ts| (ts.translation, ts.rotation)),
+            _ => None
+        };
+        states.insert(name, FrameState {
+            parent : parent.and_then(|x| buffer.get_frame_name(x)).map(|x| x.to_string()),
+            transform
+        });
+    }
+    states
+}
+
+// What changed from `old` at `old_time` to `new` at `new_time`. Works on
+// views too, through their Deref to Buffer.
+pub fn diff_buffers(old: &Buffer, old_time: &Stamp, new: &Buffer, new_time: &Stamp, tolerance: &DiffTolerance) -> FrameDiff {
+    let old_states = frame_states(old, old_time);
+    let new_states = frame_states(new, new_time);
+    let frames: BTreeSet<&String> = old_states.keys().chain(new_states.keys()).collect();
+
+    let mut changes = Vec::new();
+    for frame in frames {
+        let change = match (old_states.get(frame), new_states.get(frame)) {
+            (None, Some(state)) => Some(FrameChange::Added { frame: frame.clone(), parent: state.parent.clone() }),
+            (Some(state), None) => Some(FrameChange::Removed { frame: frame.clone(), parent: state.parent.clone() }),
+            (Some(old_state), Some(new_state)) if old_state.parent != new_state.parent => Some(FrameChange::Reparented {
+                frame      : frame.clone(),
+                old_parent : old_state.parent.clone(),
+                new_parent : new_state.parent.clone()
+            }),
+            (Some(FrameState { parent: Some(parent), transform: Some(old_transform) }),
+             Some(FrameState { transform: Some(new_transform), .. })) => {
+                let translation = (new_transform.0.vector - old_transform.0.vector).norm();
+                let rotation = old_transform.1.angle_to(&new_transform.1);
+                if translation > tolerance.translation || rotation > tolerance.rotation {
+                    Some(FrameChange::Moved { frame: frame.clone(), parent: parent.clone(), translation, rotation })
+                } else {
+                    None
+                }
+            },
+            _ => None
+        };
+        changes.extend(change);
+    }
+    FrameDiff {
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tf_buffer::tf::Transform;
+    use super::super::transform_storage::NVector3;
+
+    fn make_transform(parent: &str, child: &str, x: f64, yaw: f64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::new(NVector3::z() * yaw),
+            stamp          : Stamp::from_nanos(0)
+        }
+    }
+
+    fn make_buffer() -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.set_transform(&make_transform("base_link", "laser", 0.2, 0.0), true).unwrap();
+        buffer.set_transform(&make_transform("base_link", "camera", 0.1, 0.0), true).unwrap();
+        buffer.set_transform(&make_transform("base_link", "arm", 0.0, 0.0), true).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = make_buffer();
+        let mut new = make_buffer();
+        let view = new.snapshot();
+        new.set_transform(&make_transform("base_link", "laser", 0.25, 0.0), true).unwrap();
+        new.set_transform(&make_transform("base_link", "camera", 0.1, 1.0e-9), true).unwrap();
+        new.set_transform(&make_transform("mount", "arm", 0.0, 0.0), true).unwrap();
+        new.set_transform(&make_transform("base_link", "imu", 0.0, 0.0), true).unwrap();
+
+        let time = Stamp::from_nanos(0);
+        let diff = diff_buffers(&old, &time, &new, &time, &DiffTolerance::default());
+        assert_eq!(4, diff.changes.len());
+        assert_eq!(FrameChange::Reparented { frame: "arm".to_string(), old_parent: Some("base_link".to_string()),
+                                             new_parent: Some("mount".to_string()) }, diff.changes[0]);
+        assert_eq!(FrameChange::Added { frame: "imu".to_string(), parent: Some("base_link".to_string()) }, diff.changes[1]);
+        match &diff.changes[2] {
+            FrameChange::Moved { frame, translation, .. } => {
+                assert_eq!("laser", frame);
+                assert!(abs_diff_eq!(0.05, *translation, epsilon = 1.0e-9));
+            },
+            change => panic!("unexpected {:?}", change)
+        }
+        assert_eq!(FrameChange::Added { frame: "mount".to_string(), parent: None }, diff.changes[3]);
+
+        let text = diff.to_string();
+        assert!(text.contains("~ arm: parent base_link -> mount\n"));
+        assert!(text.contains("* laser: moved 0.050000 m and 0.000 deg relative to base_link\n"));
+
+        // the other way around, from the view taken before the changes
+        let diff = diff_buffers(&new, &time, &view, &time, &DiffTolerance::default());
+        assert!(diff.changes.contains(&FrameChange::Removed { frame: "mount".to_string(), parent: None }));
+        assert!(diff_buffers(&old, &time, &view, &time, &DiffTolerance::default()).is_empty());
+        assert_eq!("no changes\n", FrameDiff::default().to_string());
+    }
+
+}
@@ -0,0 +1,124 @@
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{FrameId, Transform};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemapRule {
+    // "base_link" -> "robot1/base_link", names already in the namespace are kept
+    AddPrefix(String),
+    // "robot1/base_link" -> "base_link", other names are kept
+    StripPrefix(String),
+    Rename(String, String)
+}
+
+fn validate(name: &str) -> Result<String, TfError> {
+    FrameId::new(name)
+        .map(|frame_id| frame_id.name)
+        .map_err(|err| TfError::InvalidArgument(format!("invalid frame id \"{}\": {}", name, err)))
+}
+
+fn validate_prefix(prefix: &str) -> Result<String, TfError> {
+    validate(prefix.trim_end_matches('/'))
+}
+
+// Rules applied in order to every frame id before it is interned, for
+// several robots sharing one graph
+#[derive(Debug, Clone, Default)]
+pub struct FrameRemapping {
+    rules : Vec<RemapRule>
+}
+
+impl FrameRemapping {
+
+    pub fn new() -> FrameRemapping {
+        FrameRemapping::default()
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Result<FrameRemapping, TfError> {
+        self.rules.push(RemapRule::AddPrefix(validate_prefix(prefix)?));
+        Ok(self)
+    }
+
+    pub fn with_stripped_prefix(mut self, prefix: &str) -> Result<FrameRemapping, TfError> {
+        self.rules.push(RemapRule::StripPrefix(validate_prefix(prefix)?));
+        Ok(self)
+    }
+
+    pub fn with_rename(mut self, from: &str, to: &str) -> Result<FrameRemapping, TfError> {
+        self.rules.push(RemapRule::Rename(validate(from)?, validate(to)?));
+        Ok(self)
+    }
+
+    pub fn rules(&self) -> &[RemapRule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn apply(&self, frame: &str) -> String {
+        let mut name = frame.trim_start_matches('/').to_string();
+        for rule in self.rules.iter() {
+            match rule {
+                RemapRule::AddPrefix(prefix) => {
+                    if !name.starts_with(&format!("{}/", prefix)) {
+                        name = format!("{}/{}", prefix, name);
+                    }
+                },
+                RemapRule::StripPrefix(prefix) => {
+                    let stripped = name.strip_prefix(&format!("{}/", prefix)).map(|x| x.to_string());
+                    if let Some(stripped) = stripped {
+                        name = stripped;
+                    }
+                },
+                RemapRule::Rename(from, to) => {
+                    if name == *from {
+                        name = to.clone();
+                    }
+                }
+            }
+        }
+        name
+    }
+
+    pub fn apply_to_transform(&self, transform: &Transform) -> Transform {
+        let mut remapped = transform.clone();
+        remapped.frame_id = self.apply(&transform.frame_id);
+        remapped.child_frame_id = self.apply(&transform.child_frame_id);
+        remapped
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_rules() {
+        let add = FrameRemapping::new().with_prefix("robot1/").unwrap();
+        assert_eq!("robot1/base_link", add.apply("base_link"));
+        assert_eq!("robot1/base_link", add.apply("/robot1/base_link"));
+
+        let strip = FrameRemapping::new().with_stripped_prefix("robot1").unwrap();
+        assert_eq!("base_link", strip.apply("robot1/base_link"));
+        assert_eq!("robot10/base_link", strip.apply("robot10/base_link"));
+    }
+
+    #[test]
+    fn test_rules_in_order() {
+        let remapping = FrameRemapping::new()
+            .with_rename("odom_combined", "odom").unwrap()
+            .with_prefix("robot2").unwrap();
+        assert_eq!("robot2/odom", remapping.apply("odom_combined"));
+        assert_eq!("robot2/map", remapping.apply("map"));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(FrameRemapping::new().with_prefix("").is_err());
+        assert!(FrameRemapping::new().with_prefix("robot 1").is_err());
+        assert!(FrameRemapping::new().with_rename("odom", "a//b").is_err());
+    }
+
+}
@@ -4,10 +4,7 @@ use approx;
 
 
 pub fn stamp_diff(a: &Stamp, b: &Stamp) -> Stamp {
-    Stamp {
-        sec: a.sec - b.sec,
-        nsec: a.nsec - b.nsec
-    }
+    Stamp::from_nanos(a.nanos() - b.nanos())
 }
 
 pub fn translation_from_vector3(v: &NVector3) -> NTranslation3 {
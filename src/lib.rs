@@ -1,23 +1,57 @@
 #![feature(uniform_paths)] 
 // extern crate rosrust;
+#[cfg(feature = "ros")]
 use rosrust;
 use nalgebra;
+#[cfg(feature = "ros")]
 #[macro_use]
 extern crate rosrust_codegen;
 #[macro_use]
 extern crate approx;
 
 
-mod tf_buffer;
-mod msg;
-mod interpolation;
-mod transform_storage;
-mod time_cache;
-mod time_cache_interface;
+pub mod tf_buffer;
+pub mod interpolation;
+pub mod transform_storage;
+#[cfg(not(feature = "ros"))]
+pub mod time;
+pub mod time_cache;
+pub mod time_cache_interface;
+pub mod static_cache;
+pub mod ring_buffer_cache;
+pub mod tiered_cache;
+pub mod disk_store;
+pub mod tf_message;
+pub mod mcap_file;
+pub mod robot_state_publisher;
+pub mod bag_file;
+pub mod listener;
+pub mod broadcaster;
+pub mod static_transform_publisher;
+pub mod clock;
+pub mod async_lookup;
+pub mod transform_watch;
+#[cfg(feature = "ros")]
+pub mod buffer_server;
+#[cfg(feature = "ros")]
+pub mod msg_conversion;
+pub mod transport;
+pub mod frame_remapping;
+pub mod trajectory;
+pub mod evaluation;
+pub mod frame_diff;
+#[cfg(feature = "capi")]
+pub mod ffi;
+#[cfg(feature = "serde")]
+pub mod serialization;
 
 use tf_buffer::tf::FrameId;
+pub use tf_buffer::tf::{Buffer, BufferView, Transform, TransformLookup};
+pub use time_cache_interface::TfError;
 
-rosmsg_include!(std_msgs/Header);
+#[cfg(feature = "ros")]
+rosmsg_include!(std_msgs/Header, tf2_msgs/TFMessage, rosgraph_msgs/Clock,
+                tf2_msgs/LookupTransformAction, actionlib_msgs/GoalStatusArray);
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,105 @@
+use super::transform_storage::Stamp;
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{self, Buffer, Transform, TransformLookup};
+use super::async_lookup::LookupTransformFuture;
+use super::transform_watch::{self, TransformWatch, WatchOptions};
+use super::transport::{TransformTransport, Subscription};
+use super::frame_remapping::FrameRemapping;
+#[cfg(feature = "ros")]
+use super::clock;
+#[cfg(feature = "ros")]
+use super::transport::RosTransformTransport;
+#[cfg(feature = "ros")]
+pub use super::msg_conversion::{transform_from_msg, transform_to_msg};
+
+use std::sync::{Arc, RwLock};
+
+fn subscribe_tf(transport: &dyn TransformTransport, buffer: Arc<RwLock<Buffer>>, remapping: FrameRemapping,
+                is_static: bool) -> Result<Subscription, TfError> {
+    transport.subscribe(is_static, Box::new(move |transforms: &[Transform], authority: Option<&str>| {
+        let mut buffer = buffer.write().unwrap();
+        for transform in transforms.iter() {
+            let transform = remapping.apply_to_transform(transform);
+            // malformed transforms from other nodes must not take the listener down
+            let _ = match authority {
+                Some(authority) => buffer.set_transform_with_authority(&transform, authority, is_static),
+                None => buffer.set_transform(&transform, is_static)
+            };
+        }
+    }))
+}
+
+// how much history the buffer of TransformListener::new keeps, like tf2
+pub const DEFAULT_CACHE_TIME_NANOS: i64 = 10_000_000_000;
+
+// Fills a shared Buffer from /tf and /tf_static for as long as it is alive
+pub struct TransformListener {
+    pub buffer              : Arc<RwLock<Buffer>>,
+    _tf_subscription        : Subscription,
+    _tf_static_subscription : Subscription
+}
+
+impl TransformListener {
+
+    // the buffer follows /clock when /use_sim_time is set, and starts over
+    // when time goes back by more than a second, e.g. when a bag loops
+    #[cfg(feature = "ros")]
+    pub fn new() -> Result<TransformListener, TfError> {
+        TransformListener::with_cache_time(Some(Stamp::from_nanos(DEFAULT_CACHE_TIME_NANOS)))
+    }
+
+    // None keeps every sample for the life of the listener
+    #[cfg(feature = "ros")]
+    pub fn with_cache_time(cache_time: Option<Stamp>) -> Result<TransformListener, TfError> {
+        let mut buffer = Buffer::with_clock(clock::ros_clock()?, cache_time);
+        buffer.set_time_jump_threshold(Some(Stamp::from_nanos(1_000_000_000)));
+        TransformListener::with_buffer(Arc::new(RwLock::new(buffer)))
+    }
+
+    #[cfg(feature = "ros")]
+    pub fn with_buffer(buffer: Arc<RwLock<Buffer>>) -> Result<TransformListener, TfError> {
+        TransformListener::with_transport(buffer, &RosTransformTransport::new())
+    }
+
+    pub fn with_transport(buffer: Arc<RwLock<Buffer>>, transport: &dyn TransformTransport) -> Result<TransformListener, TfError> {
+        TransformListener::with_remapping(buffer, transport, FrameRemapping::new())
+    }
+
+    // `remapping` only applies to what this listener receives, so that
+    // listeners on different robots' transports can share one buffer
+    pub fn with_remapping(buffer: Arc<RwLock<Buffer>>, transport: &dyn TransformTransport,
+                          remapping: FrameRemapping) -> Result<TransformListener, TfError> {
+        Ok(TransformListener {
+            _tf_subscription        : subscribe_tf(transport, buffer.clone(), remapping.clone(), false)?,
+            _tf_static_subscription : subscribe_tf(transport, buffer.clone(), remapping, true)?,
+            buffer
+        })
+    }
+
+    pub fn lookup_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> Result<Transform, TfError> {
+        self.buffer.read().unwrap().lookup_transform(target_frame, source_frame, time)
+    }
+
+    pub fn can_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> bool {
+        self.buffer.read().unwrap().can_transform(target_frame, source_frame, time)
+    }
+
+    pub async fn lookup_transform_async(&self, target_frame: &str, source_frame: &str, time: &Stamp, timeout: &Stamp) -> Result<Transform, TfError> {
+        LookupTransformFuture::new(self.buffer.clone(), target_frame, source_frame, time, timeout).await
+    }
+
+    pub fn wait_for_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp, timeout: &Stamp) -> Result<Transform, TfError> {
+        tf::wait_for_transform(&self.buffer, target_frame, source_frame, time, timeout)
+    }
+
+    pub fn watch(&self, target_frame: &str, source_frame: &str, options: WatchOptions) -> TransformWatch {
+        transform_watch::watch(&self.buffer, target_frame, source_frame, options)
+    }
+
+}
+
+impl TransformLookup for TransformListener {
+    fn lookup_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> Result<Transform, TfError> {
+        TransformListener::lookup_transform(self, target_frame, source_frame, time)
+    }
+}
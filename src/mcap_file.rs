@@ -0,0 +1,220 @@
+use super::transform_storage::Stamp;
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{Buffer, Transform};
+use super::tf_message::{self, TF_MESSAGE_DATATYPE, TF_MESSAGE_ROS2_DATATYPE,
+                        TF_MESSAGE_ROS1_DEFINITION};
+
+use mcap::records::MessageHeader;
+use mcap::{Channel, MessageStream, Schema};
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const TF_TOPIC: &str = "/tf";
+pub const TF_STATIC_TOPIC: &str = "/tf_static";
+
+const TF_MESSAGE_ROS2_DEFINITION: &str = "geometry_msgs/TransformStamped[] transforms
+================================================================================
+MSG: geometry_msgs/TransformStamped
+std_msgs/Header header
+string child_frame_id
+Transform transform
+================================================================================
+MSG: std_msgs/Header
+builtin_interfaces/Time stamp
+string frame_id
+================================================================================
+MSG: builtin_interfaces/Time
+int32 sec
+uint32 nanosec
+================================================================================
+MSG: geometry_msgs/Transform
+Vector3 translation
+Quaternion rotation
+================================================================================
+MSG: geometry_msgs/Vector3
+float64 x
+float64 y
+float64 z
+================================================================================
+MSG: geometry_msgs/Quaternion
+float64 x
+float64 y
+float64 z
+float64 w
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageEncoding {
+    Ros1,
+    Cdr
+}
+
+fn mcap_error(err: mcap::McapError) -> TfError {
+    TfError::Decode(format!("mcap: {}", err))
+}
+
+pub fn is_static_topic(topic: &str) -> bool {
+    topic.trim_start_matches('/') == "tf_static"
+}
+
+pub fn decode_tf_message(message_encoding: &str, data: &[u8]) -> Result<Option<Vec<Transform>>, TfError> {
+    match message_encoding {
+        "ros1" => Ok(Some(tf_message::decode_ros1(data)?)),
+        "cdr" => Ok(Some(tf_message::decode_cdr(data)?)),
+        _ => Ok(None)
+    }
+}
+
+// Inserts every tf2_msgs/TFMessage found in the mcap data into the buffer,
+// returns how many transforms were inserted
+pub fn read_mcap_bytes(data: &[u8], buffer: &mut Buffer) -> Result<usize, TfError> {
+    let mut inserted = 0;
+    for message in MessageStream::new(data).map_err(mcap_error)? {
+        let message = message.map_err(mcap_error)?;
+        let is_tf = match message.channel.schema {
+            Some(ref schema) => tf_message::is_tf_message_datatype(&schema.name),
+            None => false
+        };
+        if !is_tf {
+            continue;
+        }
+        let is_static = is_static_topic(&message.channel.topic);
+        // ros1 recordings converted to mcap keep the publisher in the channel metadata
+        let callerid = message.channel.metadata.get("callerid");
+        if let Some(transforms) = decode_tf_message(&message.channel.message_encoding, &message.data)? {
+            for transform in transforms.iter() {
+                match callerid {
+                    Some(callerid) => buffer.set_transform_with_authority(transform, callerid, is_static)?,
+                    None => buffer.set_transform(transform, is_static)?
+                }
+                inserted += 1;
+            }
+        }
+    }
+    Ok(inserted)
+}
+
+pub fn read_mcap<P: AsRef<Path>>(path: P, buffer: &mut Buffer) -> Result<usize, TfError> {
+    let data = std::fs::read(path)?;
+    read_mcap_bytes(&data, buffer)
+}
+
+fn make_channel(topic: &str, encoding: MessageEncoding) -> Channel<'static> {
+    let schema = match encoding {
+        MessageEncoding::Ros1 => Schema {
+            name     : TF_MESSAGE_DATATYPE.to_string(),
+            encoding : "ros1msg".to_string(),
+            data     : Cow::Borrowed(TF_MESSAGE_ROS1_DEFINITION.as_bytes())
+        },
+        MessageEncoding::Cdr => Schema {
+            name     : TF_MESSAGE_ROS2_DATATYPE.to_string(),
+            encoding : "ros2msg".to_string(),
+            data     : Cow::Borrowed(TF_MESSAGE_ROS2_DEFINITION.as_bytes())
+        }
+    };
+    Channel {
+        topic            : topic.to_string(),
+        schema           : Some(Arc::new(schema)),
+        message_encoding : match encoding {
+            MessageEncoding::Ros1 => "ros1".to_string(),
+            MessageEncoding::Cdr => "cdr".to_string()
+        },
+        metadata         : BTreeMap::new()
+    }
+}
+
+// Writes the whole history of the buffer, one TFMessage per sample,
+// dynamic frames on /tf and static frames on /tf_static
+pub fn write_mcap_to<W: Write + Seek>(writer: W, buffer: &Buffer, encoding: MessageEncoding) -> Result<(), TfError> {
+    let mut out = mcap::Writer::new(writer).map_err(mcap_error)?;
+    let tf_channel = out.add_channel(&make_channel(TF_TOPIC, encoding)).map_err(mcap_error)?;
+    let tf_static_channel = out.add_channel(&make_channel(TF_STATIC_TOPIC, encoding)).map_err(mcap_error)?;
+
+    let mut transforms = buffer.get_all_transforms();
+    transforms.sort_by_key(|x| x.0.stamp.nanos());
+
+    let mut sequence = 0u32;
+    for (transform, is_static) in transforms.iter() {
+        let data = match encoding {
+            MessageEncoding::Ros1 => tf_message::encode_ros1(std::slice::from_ref(transform)),
+            MessageEncoding::Cdr => tf_message::encode_cdr(std::slice::from_ref(transform))
+        };
+        let log_time = stamp_to_log_time(&transform.stamp);
+        let header = MessageHeader {
+            channel_id   : if *is_static { tf_static_channel } else { tf_channel },
+            sequence,
+            log_time,
+            publish_time : log_time
+        };
+        out.write_to_known_channel(&header, &data).map_err(mcap_error)?;
+        sequence += 1;
+    }
+
+    out.finish().map_err(mcap_error)?;
+    Ok(())
+}
+
+pub fn write_mcap<P: AsRef<Path>>(path: P, buffer: &Buffer, encoding: MessageEncoding) -> Result<(), TfError> {
+    let file = File::create(path)?;
+    write_mcap_to(BufWriter::new(file), buffer, encoding)
+}
+
+fn stamp_to_log_time(stamp: &Stamp) -> u64 {
+    let nanos = stamp.nanos();
+    if nanos < 0 { 0 } else { nanos as u64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{NTranslation3, NQuaternion};
+    use std::io::Cursor;
+
+    fn make_buffer() -> Buffer {
+        let mut buffer = Buffer::new();
+        for i in 1..4 {
+            buffer.set_transform(&Transform {
+                frame_id       : "odom".to_string(),
+                child_frame_id : "base_link".to_string(),
+                translation    : NTranslation3::new(i as f64, 0.0, 0.0),
+                rotation       : NQuaternion::identity(),
+                stamp          : Stamp::from_nanos(i * 1_000_000_000)
+            }, false).unwrap();
+        }
+        buffer.set_transform(&Transform {
+            frame_id       : "base_link".to_string(),
+            child_frame_id : "laser".to_string(),
+            translation    : NTranslation3::new(0.0, 0.0, 1.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(0)
+        }, true).unwrap();
+        buffer
+    }
+
+    fn check_round_trip(encoding: MessageEncoding) {
+        let mut cursor = Cursor::new(Vec::new());
+        write_mcap_to(&mut cursor, &make_buffer(), encoding).unwrap();
+
+        let mut buffer = Buffer::new();
+        assert_eq!(4, read_mcap_bytes(cursor.get_ref(), &mut buffer).unwrap());
+        let res = buffer.lookup_transform("odom", "laser", &Stamp::from_nanos(2_500_000_000)).unwrap();
+        assert!(abs_diff_eq!(2.5, res.translation.vector.x));
+        assert!(abs_diff_eq!(1.0, res.translation.vector.z));
+    }
+
+    #[test]
+    fn test_round_trip_ros1() {
+        check_round_trip(MessageEncoding::Ros1);
+    }
+
+    #[test]
+    fn test_round_trip_cdr() {
+        check_round_trip(MessageEncoding::Cdr);
+    }
+
+}
@@ -1 +0,0 @@
-rosmsg_include!(std_msgs/Header);
\ No newline at end of file
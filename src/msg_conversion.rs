@@ -0,0 +1,34 @@
+use super::transform_storage::{Stamp, NTranslation3, NQuaternion};
+use super::tf_buffer::tf::Transform;
+use super::msg::geometry_msgs::TransformStamped;
+
+use nalgebra::Quaternion;
+
+pub fn transform_from_msg(msg: &TransformStamped) -> Transform {
+    let t = &msg.transform.translation;
+    let q = &msg.transform.rotation;
+    Transform {
+        frame_id       : msg.header.frame_id.clone(),
+        child_frame_id : msg.child_frame_id.clone(),
+        translation    : NTranslation3::new(t.x, t.y, t.z),
+        rotation       : NQuaternion::from_quaternion(Quaternion::new(q.w, q.x, q.y, q.z)),
+        stamp          : Stamp::from_nanos(msg.header.stamp.nanos())
+    }
+}
+
+pub fn transform_to_msg(transform: &Transform) -> TransformStamped {
+    let mut msg = TransformStamped::default();
+    msg.header.frame_id = transform.frame_id.clone();
+    msg.header.stamp = rosrust::Time::from_nanos(transform.stamp.nanos());
+    msg.child_frame_id = transform.child_frame_id.clone();
+    let t = &transform.translation.vector;
+    msg.transform.translation.x = t.x;
+    msg.transform.translation.y = t.y;
+    msg.transform.translation.z = t.z;
+    let q = &transform.rotation.coords;
+    msg.transform.rotation.x = q.x;
+    msg.transform.rotation.y = q.y;
+    msg.transform.rotation.z = q.z;
+    msg.transform.rotation.w = q.w;
+    msg
+}
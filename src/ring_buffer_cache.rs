@@ -0,0 +1,390 @@
+use super::transform_storage::{FrameId, TransformStorage, Stamp, NTranslation3, NQuaternion};
+use super::interpolation::interpolate_two_transform;
+use super::time_cache_interface::*;
+
+use nalgebra::Quaternion;
+
+use std::sync::Arc;
+use std::sync::atomic::{fence, AtomicI64, AtomicU32, AtomicU64, Ordering};
+
+use TfError::*;
+
+// One sample stored as atomics, guarded by a sequence number: odd while
+// the writer is filling it, 2 * generation + 2 once generation is complete
+#[derive(Default)]
+struct Slot {
+    seq            : AtomicU64,
+    frame_id       : AtomicU32,
+    child_frame_id : AtomicU32,
+    stamp          : AtomicI64,
+    // translation xyz, then rotation ijkw, as f64 bits
+    values         : [AtomicU64; 7]
+}
+
+struct RingShared {
+    slots : Box<[Slot]>,
+    // generation of the next insert
+    head  : AtomicU64,
+    // first generation that was not cleared or pruned
+    start : AtomicU64
+}
+
+enum Closest {
+    One(TransformStorage),
+    // newer sample first, like in TimeCache
+    Two(TransformStorage, TransformStorage)
+}
+
+impl RingShared {
+
+    fn capacity(&self) -> u64 {
+        self.slots.len() as u64
+    }
+
+    fn slot(&self, generation: u64) -> &Slot {
+        &self.slots[(generation % self.capacity()) as usize]
+    }
+
+    // only ever called by the single writer
+    fn store(&self, generation: u64, ts: &TransformStorage) {
+        let slot = self.slot(generation);
+        slot.seq.store(2 * generation + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.frame_id.store(ts.frame_id, Ordering::Relaxed);
+        slot.child_frame_id.store(ts.child_frame_id, Ordering::Relaxed);
+        slot.stamp.store(ts.stamp.nanos(), Ordering::Relaxed);
+        let t = &ts.translation.vector;
+        let q = &ts.rotation.coords;
+        let values = [t.x, t.y, t.z, q.x, q.y, q.z, q.w];
+        for (value, stored) in values.iter().zip(slot.values.iter()) {
+            stored.store(value.to_bits(), Ordering::Relaxed);
+        }
+        slot.seq.store(2 * generation + 2, Ordering::Release);
+    }
+
+    // None if the slot is being written or already holds a newer generation
+    fn load(&self, generation: u64) -> Option<TransformStorage> {
+        let slot = self.slot(generation);
+        let seq = slot.seq.load(Ordering::Acquire);
+        if seq != 2 * generation + 2 {
+            return None;
+        }
+        let frame_id = slot.frame_id.load(Ordering::Relaxed);
+        let child_frame_id = slot.child_frame_id.load(Ordering::Relaxed);
+        let stamp = slot.stamp.load(Ordering::Relaxed);
+        let mut values = [0.0; 7];
+        for (value, stored) in values.iter_mut().zip(slot.values.iter()) {
+            *value = f64::from_bits(stored.load(Ordering::Relaxed));
+        }
+        fence(Ordering::Acquire);
+        if slot.seq.load(Ordering::Relaxed) != seq {
+            return None;
+        }
+        Some(TransformStorage {
+            frame_id,
+            child_frame_id,
+            translation    : NTranslation3::new(values[0], values[1], values[2]),
+            rotation       : NQuaternion::new_unchecked(Quaternion::new(values[6], values[3], values[4], values[5])),
+            stamp          : Stamp::from_nanos(stamp)
+        })
+    }
+
+    // live generations as [oldest, head)
+    fn bounds(&self) -> (u64, u64) {
+        let start = self.start.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        (start.max(head.saturating_sub(self.capacity())).min(head), head)
+    }
+
+    // None when the writer overwrote a sample under us, the caller retries
+    fn try_find_closest(&self, stamp: &Stamp) -> Option<Result<Closest, TfError>> {
+        let (oldest, head) = self.bounds();
+        if oldest == head {
+            return Some(Err(TransformNotFound));
+        }
+        let newest = self.load(head - 1)?;
+        if stamp.nanos() == 0 || newest.stamp == *stamp {
+            return Some(Ok(Closest::One(newest)));
+        }
+        if head - oldest == 1 {
+            return Some(Err(ExtrapolationError1));
+        }
+        if *stamp > newest.stamp {
+            return Some(Err(ExtrapolationError3));
+        }
+        let oldest_ts = self.load(oldest)?;
+        if oldest_ts.stamp == *stamp {
+            return Some(Ok(Closest::One(oldest_ts)));
+        }
+        if *stamp < oldest_ts.stamp {
+            return Some(Err(ExtrapolationError2));
+        }
+
+        // stamp(older) < stamp < stamp(newer)
+        let (mut older, mut newer) = (oldest, head - 1);
+        let (mut older_ts, mut newer_ts) = (oldest_ts, newest);
+        while newer - older > 1 {
+            let middle = older + (newer - older) / 2;
+            let middle_ts = self.load(middle)?;
+            if middle_ts.stamp == *stamp {
+                return Some(Ok(Closest::One(middle_ts)));
+            } else if middle_ts.stamp < *stamp {
+                older = middle;
+                older_ts = middle_ts;
+            } else {
+                newer = middle;
+                newer_ts = middle_ts;
+            }
+        }
+        Some(Ok(Closest::Two(newer_ts, older_ts)))
+    }
+
+    fn find_closest(&self, stamp: &Stamp) -> Result<Closest, TfError> {
+        loop {
+            if let Some(res) = self.try_find_closest(stamp) {
+                return res;
+            }
+        }
+    }
+
+    fn get_data(&self, stamp: &Stamp) -> Result<TransformStorage, TfError> {
+        match self.find_closest(stamp)? {
+            Closest::One(ts) => Ok(ts),
+            Closest::Two(newer_ts, older_ts) => {
+                if newer_ts.frame_id == older_ts.frame_id {
+                    Ok(interpolate_two_transform(&newer_ts, &older_ts, stamp))
+                } else {
+                    let mut ts = older_ts;
+                    ts.stamp = stamp.clone();
+                    Ok(ts)
+                }
+            }
+        }
+    }
+
+    fn get_parent(&self, stamp: &Stamp) -> Result<FrameId, TfError> {
+        match self.find_closest(stamp) {
+            Ok(Closest::One(ts)) => Ok(ts.frame_id),
+            Ok(Closest::Two(_, older_ts)) => Ok(older_ts.frame_id),
+            Err(TransformNotFound) => Err(NoParent),
+            Err(err) => Err(err)
+        }
+    }
+
+    fn latest(&self) -> Option<TransformStorage> {
+        loop {
+            let (oldest, head) = self.bounds();
+            if oldest == head {
+                return None;
+            }
+            if let Some(ts) = self.load(head - 1) {
+                return Some(ts);
+            }
+        }
+    }
+
+}
+
+// Fixed-capacity cache for real-time loops: all memory is allocated up
+// front, inserts overwrite the oldest sample and neither inserts nor
+// lookups allocate. Samples older than the newest one are rejected
+// instead of being sorted in.
+pub struct RingBufferCache {
+    shared : Arc<RingShared>
+}
+
+impl RingBufferCache {
+
+    pub fn new(capacity: usize) -> RingBufferCache {
+        assert!(capacity > 0, "a ring buffer cache needs room for at least one sample");
+        RingBufferCache {
+            shared : Arc::new(RingShared {
+                slots : (0..capacity).map(|_| Slot::default()).collect::<Vec<Slot>>().into_boxed_slice(),
+                head  : AtomicU64::new(0),
+                start : AtomicU64::new(0)
+            })
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+
+    // A handle for other threads that looks up without any lock, while
+    // this cache keeps inserting
+    pub fn reader(&self) -> RingBufferReader {
+        RingBufferReader {
+            shared : self.shared.clone()
+        }
+    }
+
+}
+
+impl TimeCacheInterface for RingBufferCache {
+
+    fn get_data(&self, stamp: &Stamp) -> Result<TransformStorage, TfError> {
+        self.shared.get_data(stamp)
+    }
+
+    fn get_parent(&self, stamp: &Stamp) -> Result<FrameId, TfError> {
+        self.shared.get_parent(stamp)
+    }
+
+    fn insert_data(&mut self, new_ts: TransformStorage) -> bool {
+        if let Some(latest) = self.shared.latest() {
+            if new_ts.stamp < latest.stamp {
+                return false;
+            }
+        }
+        let head = self.shared.head.load(Ordering::Relaxed);
+        self.shared.store(head, &new_ts);
+        self.shared.head.store(head + 1, Ordering::Release);
+        true
+    }
+
+    fn clear(&mut self) {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        self.shared.start.store(head, Ordering::Release);
+    }
+
+    fn get_latest_time_and_parent(&self) -> Option<(Stamp, FrameId)> {
+        let ts = self.shared.latest()?;
+        Some((ts.stamp, ts.frame_id))
+    }
+
+    fn get_length(&self) -> usize {
+        let (oldest, head) = self.shared.bounds();
+        (head - oldest) as usize
+    }
+
+    fn get_latest_timestamp(&self) -> Option<Stamp> {
+        Some(self.shared.latest()?.stamp)
+    }
+
+    fn get_oldest_timestamp(&self) -> Option<Stamp> {
+        let (oldest, head) = self.shared.bounds();
+        if oldest == head {
+            return None;
+        }
+        Some(self.shared.load(oldest)?.stamp)
+    }
+
+    fn get_all_data(&self) -> Vec<TransformStorage> {
+        let (oldest, head) = self.shared.bounds();
+        (oldest..head).filter_map(|generation| self.shared.load(generation)).collect()
+    }
+
+    fn prune_older_than(&mut self, stamp: &Stamp) {
+        let (mut oldest, head) = self.shared.bounds();
+        while head - oldest > 1 && self.shared.load(oldest).map_or(false, |ts| ts.stamp < *stamp) {
+            oldest += 1;
+        }
+        self.shared.start.store(oldest, Ordering::Release);
+    }
+
+    fn clone_cache(&self) -> Box<dyn TimeCacheInterface> {
+        let mut cache = RingBufferCache::new(self.capacity());
+        for ts in self.get_all_data() {
+            cache.insert_data(ts);
+        }
+        Box::new(cache)
+    }
+
+}
+
+// Lock-free lookups into a RingBufferCache owned by another thread
+#[derive(Clone)]
+pub struct RingBufferReader {
+    shared : Arc<RingShared>
+}
+
+impl RingBufferReader {
+
+    pub fn get_data(&self, stamp: &Stamp) -> Result<TransformStorage, TfError> {
+        self.shared.get_data(stamp)
+    }
+
+    pub fn get_parent(&self, stamp: &Stamp) -> Result<FrameId, TfError> {
+        self.shared.get_parent(stamp)
+    }
+
+    pub fn get_latest(&self) -> Option<TransformStorage> {
+        self.shared.latest()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_transform_storage(parent: FrameId, x: f64, nanos: i64) -> TransformStorage {
+        TransformStorage {
+            frame_id       : parent,
+            child_frame_id : 2u32,
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    #[test]
+    fn test_overwrites_oldest() {
+        let mut cache = RingBufferCache::new(3);
+        for i in 1..6 {
+            assert!(cache.insert_data(make_transform_storage(1, i as f64, i * 100)));
+        }
+        assert!(!cache.insert_data(make_transform_storage(1, 0.0, 50)));
+        assert_eq!(3, cache.get_length());
+        assert_eq!(Some(Stamp::from_nanos(300)), cache.get_oldest_timestamp());
+        assert!(cache.get_data(&Stamp::from_nanos(250)).is_err());
+
+        let ts = cache.get_data(&Stamp::from_nanos(450)).unwrap();
+        assert!(abs_diff_eq!(4.5, ts.translation.vector.x));
+        assert_eq!(Stamp::from_nanos(450), ts.stamp);
+        assert!(abs_diff_eq!(5.0, cache.get_data(&Stamp::from_nanos(0)).unwrap().translation.vector.x));
+
+        cache.prune_older_than(&Stamp::from_nanos(400));
+        assert_eq!(2, cache.get_length());
+        cache.clear();
+        assert_eq!(0, cache.get_length());
+        assert!(cache.get_latest_timestamp().is_none());
+    }
+
+    #[test]
+    fn test_reparenting() {
+        let mut cache = RingBufferCache::new(8);
+        cache.insert_data(make_transform_storage(1, 1.0, 100));
+        cache.insert_data(make_transform_storage(5, 2.0, 200));
+        assert_eq!(1u32, cache.get_parent(&Stamp::from_nanos(150)).unwrap());
+        assert_eq!(5u32, cache.get_parent(&Stamp::from_nanos(200)).unwrap());
+        assert!(abs_diff_eq!(1.0, cache.get_data(&Stamp::from_nanos(150)).unwrap().translation.vector.x));
+    }
+
+    #[test]
+    fn test_concurrent_reader() {
+        let mut cache = RingBufferCache::new(16);
+        cache.insert_data(make_transform_storage(1, 1.0, 1));
+        let reader = cache.reader();
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let reader_done = done.clone();
+        let handle = std::thread::spawn(move || {
+            while !reader_done.load(Ordering::Relaxed) {
+                let latest = reader.get_latest().unwrap();
+                // every sample has x equal to its stamp
+                assert!(abs_diff_eq!(latest.stamp.nanos() as f64, latest.translation.vector.x));
+                let middle = Stamp::from_nanos(latest.stamp.nanos() - 5);
+                if let Ok(ts) = reader.get_data(&middle) {
+                    assert!(abs_diff_eq!(middle.nanos() as f64, ts.translation.vector.x, epsilon = 1.0e-6));
+                }
+            }
+        });
+        for i in 2..20000 {
+            cache.insert_data(make_transform_storage(1, i as f64, i));
+        }
+        done.store(true, Ordering::Relaxed);
+        handle.join().unwrap();
+    }
+
+}
@@ -0,0 +1,300 @@
+use super::transform_storage::{Stamp, NVector3, NTranslation3, NQuaternion};
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{Buffer, Transform};
+
+use nalgebra::{Isometry3, Unit};
+
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JointType {
+    Fixed,
+    Revolute,
+    Continuous,
+    Prismatic,
+    // floating and planar joints carry no single position, robot_state_publisher
+    // leaves them to whoever owns the state (usually localization)
+    Floating,
+    Planar
+}
+
+#[derive(Debug, Clone)]
+pub struct Mimic {
+    pub joint      : String,
+    pub multiplier : f64,
+    pub offset     : f64
+}
+
+#[derive(Debug, Clone)]
+pub struct UrdfJoint {
+    pub name        : String,
+    pub joint_type  : JointType,
+    pub parent_link : String,
+    pub child_link  : String,
+    pub origin      : Isometry3<f64>,
+    pub axis        : Unit<NVector3>,
+    pub mimic       : Option<Mimic>
+}
+
+impl UrdfJoint {
+
+    pub fn transform_at(&self, position: f64) -> Isometry3<f64> {
+        match self.joint_type {
+            JointType::Revolute | JointType::Continuous => {
+                self.origin * NQuaternion::from_axis_angle(&self.axis, position)
+            },
+            JointType::Prismatic => {
+                self.origin * NTranslation3::from(self.axis.into_inner() * position)
+            },
+            _ => self.origin
+        }
+    }
+
+}
+
+fn parse_vector(text: Option<&str>, default: NVector3) -> Result<NVector3, TfError> {
+    let text = match text {
+        Some(text) => text,
+        None => return Ok(default)
+    };
+    let values = text.split_whitespace()
+        .map(|x| x.parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| TfError::Decode(format!("invalid vector '{}' in urdf", text)))?;
+    if values.len() != 3 {
+        return Err(TfError::Decode(format!("expected 3 values in '{}'", text)));
+    }
+    Ok(NVector3::new(values[0], values[1], values[2]))
+}
+
+fn parse_joint(node: roxmltree::Node) -> Result<UrdfJoint, TfError> {
+    let name = node.attribute("name")
+        .ok_or_else(|| TfError::Decode("urdf joint without a name".to_string()))?;
+    let joint_type = match node.attribute("type") {
+        Some("fixed") => JointType::Fixed,
+        Some("revolute") => JointType::Revolute,
+        Some("continuous") => JointType::Continuous,
+        Some("prismatic") => JointType::Prismatic,
+        Some("floating") => JointType::Floating,
+        Some("planar") => JointType::Planar,
+        other => return Err(TfError::Decode(format!("joint '{}' has unknown type {:?}", name, other)))
+    };
+    let child_element = |tag: &str| node.children().find(|x| x.has_tag_name(tag));
+    let link_of = |tag: &str| -> Result<String, TfError> {
+        child_element(tag)
+            .and_then(|x| x.attribute("link"))
+            .map(|x| x.to_string())
+            .ok_or_else(|| TfError::Decode(format!("joint '{}' has no {} link", name, tag)))
+    };
+
+    let origin = child_element("origin");
+    let xyz = parse_vector(origin.and_then(|x| x.attribute("xyz")), NVector3::zeros())?;
+    let rpy = parse_vector(origin.and_then(|x| x.attribute("rpy")), NVector3::zeros())?;
+    let axis = parse_vector(child_element("axis").and_then(|x| x.attribute("xyz")), NVector3::x())?;
+    let axis = Unit::try_new(axis, 1.0e-9)
+        .ok_or_else(|| TfError::Decode(format!("joint '{}' has a zero axis", name)))?;
+
+    let mimic = match child_element("mimic") {
+        Some(mimic) => {
+            let number = |attr: &str, default: f64| -> Result<f64, TfError> {
+                match mimic.attribute(attr) {
+                    Some(text) => text.parse().map_err(|_| TfError::Decode(format!("invalid mimic {} '{}'", attr, text))),
+                    None => Ok(default)
+                }
+            };
+            Some(Mimic {
+                joint      : mimic.attribute("joint")
+                                 .ok_or_else(|| TfError::Decode(format!("mimic of joint '{}' has no joint", name)))?
+                                 .to_string(),
+                multiplier : number("multiplier", 1.0)?,
+                offset     : number("offset", 0.0)?
+            })
+        },
+        None => None
+    };
+
+    Ok(UrdfJoint {
+        name        : name.to_string(),
+        joint_type,
+        parent_link : link_of("parent")?,
+        child_link  : link_of("child")?,
+        origin      : Isometry3::from_parts(NTranslation3::from(xyz),
+                                            NQuaternion::from_euler_angles(rpy.x, rpy.y, rpy.z)),
+        axis,
+        mimic
+    })
+}
+
+pub fn parse_urdf(xml: &str) -> Result<Vec<UrdfJoint>, TfError> {
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|err| TfError::Decode(format!("invalid urdf: {}", err)))?;
+    let robot = document.root_element();
+    if !robot.has_tag_name("robot") {
+        return Err(TfError::Decode("urdf root element is not <robot>".to_string()));
+    }
+    robot.children()
+        .filter(|x| x.has_tag_name("joint"))
+        .map(parse_joint)
+        .collect()
+}
+
+pub struct RobotStatePublisher {
+    joints : Vec<UrdfJoint>,
+    prefix : String
+}
+
+impl RobotStatePublisher {
+
+    pub fn new(joints: Vec<UrdfJoint>) -> RobotStatePublisher {
+        RobotStatePublisher {
+            joints,
+            prefix : String::new()
+        }
+    }
+
+    pub fn from_urdf(xml: &str) -> Result<RobotStatePublisher, TfError> {
+        Ok(RobotStatePublisher::new(parse_urdf(xml)?))
+    }
+
+    pub fn from_urdf_file<P: AsRef<Path>>(path: P) -> Result<RobotStatePublisher, TfError> {
+        RobotStatePublisher::from_urdf(&std::fs::read_to_string(path)?)
+    }
+
+    // like the tf_prefix parameter of robot_state_publisher
+    pub fn with_prefix<S: Into<String>>(mut self, prefix: S) -> RobotStatePublisher {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn joints(&self) -> &[UrdfJoint] {
+        &self.joints
+    }
+
+    fn make_transform(&self, joint: &UrdfJoint, transform: Isometry3<f64>, stamp: &Stamp) -> Transform {
+        Transform {
+            frame_id       : format!("{}{}", self.prefix, joint.parent_link),
+            child_frame_id : format!("{}{}", self.prefix, joint.child_link),
+            translation    : transform.translation,
+            rotation       : transform.rotation,
+            stamp          : stamp.clone()
+        }
+    }
+
+    pub fn fixed_transforms(&self, stamp: &Stamp) -> Vec<Transform> {
+        self.joints.iter()
+            .filter(|x| x.joint_type == JointType::Fixed)
+            .map(|x| self.make_transform(x, x.origin, stamp))
+            .collect()
+    }
+
+    // `names` and `positions` are the fields of a sensor_msgs/JointState,
+    // joints without a position are skipped
+    pub fn joint_transforms(&self, names: &[String], positions: &[f64], stamp: &Stamp) -> Vec<Transform> {
+        let positions: HashMap<&str, f64> = names.iter()
+            .map(|x| x.as_str())
+            .zip(positions.iter().cloned())
+            .collect();
+
+        let mut transforms = Vec::new();
+        for joint in self.joints.iter() {
+            match joint.joint_type {
+                JointType::Revolute | JointType::Continuous | JointType::Prismatic => {},
+                _ => continue
+            }
+            let position = match joint.mimic {
+                Some(ref mimic) => positions.get(mimic.joint.as_str()).map(|x| x * mimic.multiplier + mimic.offset),
+                None => positions.get(joint.name.as_str()).cloned()
+            };
+            if let Some(position) = position {
+                transforms.push(self.make_transform(joint, joint.transform_at(position), stamp));
+            }
+        }
+        transforms
+    }
+
+    pub fn set_fixed_transforms(&self, buffer: &mut Buffer) -> Result<(), TfError> {
+        for transform in self.fixed_transforms(&Stamp::from_nanos(0)).iter() {
+            buffer.set_transform(transform, true)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_joint_transforms(&self, buffer: &mut Buffer, names: &[String], positions: &[f64], stamp: &Stamp) -> Result<(), TfError> {
+        for transform in self.joint_transforms(names, positions, stamp).iter() {
+            buffer.set_transform(transform, false)?;
+        }
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URDF: &str = r#"<?xml version="1.0"?>
+<robot name="test">
+  <link name="base_link"/>
+  <link name="laser"/>
+  <link name="arm"/>
+  <link name="slider"/>
+  <link name="finger"/>
+  <joint name="laser_joint" type="fixed">
+    <parent link="base_link"/>
+    <child link="laser"/>
+    <origin xyz="0.1 0 0.3" rpy="0 0 0"/>
+  </joint>
+  <joint name="arm_joint" type="revolute">
+    <parent link="base_link"/>
+    <child link="arm"/>
+    <origin xyz="0 0 1"/>
+    <axis xyz="0 0 1"/>
+  </joint>
+  <joint name="slider_joint" type="prismatic">
+    <parent link="arm"/>
+    <child link="slider"/>
+    <axis xyz="1 0 0"/>
+  </joint>
+  <joint name="finger_joint" type="prismatic">
+    <parent link="slider"/>
+    <child link="finger"/>
+    <axis xyz="0 1 0"/>
+    <mimic joint="slider_joint" multiplier="2.0"/>
+  </joint>
+</robot>"#;
+
+    #[test]
+    fn test_parse_urdf() {
+        let joints = parse_urdf(URDF).unwrap();
+        assert_eq!(4, joints.len());
+        assert_eq!(JointType::Fixed, joints[0].joint_type);
+        assert_eq!("base_link", joints[0].parent_link);
+        assert_eq!("laser", joints[0].child_link);
+        assert!(abs_diff_eq!(0.3, joints[0].origin.translation.vector.z));
+        assert_eq!("slider_joint", joints[3].mimic.as_ref().unwrap().joint);
+    }
+
+    #[test]
+    fn test_publish_into_buffer() {
+        let publisher = RobotStatePublisher::from_urdf(URDF).unwrap();
+        let mut buffer = Buffer::new();
+        publisher.set_fixed_transforms(&mut buffer).unwrap();
+        let names = vec!["arm_joint".to_string(), "slider_joint".to_string()];
+        let positions = vec![std::f64::consts::FRAC_PI_2, 0.5];
+        publisher.set_joint_transforms(&mut buffer, &names, &positions, &Stamp::from_nanos(100)).unwrap();
+
+        let laser = buffer.lookup_transform("base_link", "laser", &Stamp::from_nanos(0)).unwrap();
+        assert!(abs_diff_eq!(0.1, laser.translation.vector.x));
+
+        // arm turned by 90 degrees, so the slider moves along base_link y
+        let slider = buffer.lookup_transform("base_link", "slider", &Stamp::from_nanos(100)).unwrap();
+        assert!(abs_diff_eq!(0.0, slider.translation.vector.x, epsilon = 1.0e-9));
+        assert!(abs_diff_eq!(0.5, slider.translation.vector.y, epsilon = 1.0e-9));
+        assert!(abs_diff_eq!(1.0, slider.translation.vector.z, epsilon = 1.0e-9));
+
+        let finger = buffer.lookup_transform("slider", "finger", &Stamp::from_nanos(100)).unwrap();
+        assert!(abs_diff_eq!(1.0, finger.translation.vector.y, epsilon = 1.0e-9));
+    }
+
+}
@@ -0,0 +1,122 @@
+// serde adapters for the field types we do not own: stamps are encoded as
+// integer nanoseconds, translations as [x, y, z] and rotations as [x, y, z, w]
+use super::transform_storage::{Stamp, NTranslation3, NQuaternion, TransformStorage};
+
+use nalgebra::Quaternion;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::collections::VecDeque;
+
+pub mod stamp_nanos {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(stamp: &Stamp, serializer: S) -> Result<S::Ok, S::Error> {
+        stamp.nanos().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Stamp, D::Error> {
+        Ok(Stamp::from_nanos(i64::deserialize(deserializer)?))
+    }
+}
+
+pub mod translation_xyz {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(translation: &NTranslation3, serializer: S) -> Result<S::Ok, S::Error> {
+        let v = &translation.vector;
+        [v.x, v.y, v.z].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NTranslation3, D::Error> {
+        let [x, y, z] = <[f64; 3]>::deserialize(deserializer)?;
+        Ok(NTranslation3::new(x, y, z))
+    }
+}
+
+pub mod rotation_xyzw {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(rotation: &NQuaternion, serializer: S) -> Result<S::Ok, S::Error> {
+        let q = &rotation.coords;
+        [q.x, q.y, q.z, q.w].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NQuaternion, D::Error> {
+        let [x, y, z, w] = <[f64; 4]>::deserialize(deserializer)?;
+        Ok(NQuaternion::from_quaternion(Quaternion::new(w, x, y, z)))
+    }
+}
+
+// TimeCache lookups rely on the samples being newest first, which a
+// hand-written or edited file does not guarantee
+pub fn newest_first<'de, D: Deserializer<'de>>(deserializer: D) -> Result<VecDeque<TransformStorage>, D::Error> {
+    let mut transforms = Vec::<TransformStorage>::deserialize(deserializer)?;
+    transforms.sort_by_key(|x| std::cmp::Reverse(x.stamp.nanos()));
+    Ok(transforms.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::TransformStorage;
+    use super::super::time_cache_interface::{TimeCache, TimeCacheInterface};
+    use super::super::tf_buffer::tf::{Buffer, BufferSnapshot, Transform};
+
+    fn make_transform_storage(nanos: i64, x: f64) -> TransformStorage {
+        TransformStorage {
+            frame_id       : 1u32,
+            child_frame_id : 2u32,
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::from_quaternion(Quaternion::new(0.5, 0.5, 0.5, 0.5)),
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    #[test]
+    fn test_transform_storage_encoding() {
+        let json = serde_json::to_value(&make_transform_storage(1_500_000_000, 1.0)).unwrap();
+        assert_eq!(1_500_000_000i64, json["stamp"].as_i64().unwrap());
+        assert_eq!(serde_json::json!([1.0, 0.0, 0.0]), json["translation"]);
+        assert_eq!(serde_json::json!([0.5, 0.5, 0.5, 0.5]), json["rotation"]);
+    }
+
+    #[test]
+    fn test_time_cache_round_trip() {
+        let mut cache = TimeCache::new();
+        cache.insert_data(make_transform_storage(100, 1.0));
+        cache.insert_data(make_transform_storage(200, 2.0));
+
+        let json = serde_json::to_string(&cache).unwrap();
+        let restored: TimeCache = serde_json::from_str(&json).unwrap();
+        assert_eq!(2usize, restored.get_length());
+        let ts = restored.get_data(&Stamp::from_nanos(150)).unwrap();
+        assert!(abs_diff_eq!(1.5, ts.translation.vector.x));
+
+        // oldest first, as someone may write a fixture by hand
+        let mut json = serde_json::to_value(&cache).unwrap();
+        json["transforms_ordered"].as_array_mut().unwrap().reverse();
+        let restored: TimeCache = serde_json::from_value(json).unwrap();
+        assert_eq!(Some(Stamp::from_nanos(200)), restored.get_latest_timestamp());
+        let ts = restored.get_data(&Stamp::from_nanos(150)).unwrap();
+        assert!(abs_diff_eq!(1.5, ts.translation.vector.x));
+    }
+
+    #[test]
+    fn test_buffer_snapshot_round_trip() {
+        let mut buffer = Buffer::new();
+        buffer.set_transform(&Transform {
+            frame_id       : "map".to_string(),
+            child_frame_id : "odom".to_string(),
+            translation    : NTranslation3::new(1.0, 2.0, 3.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(0)
+        }, true).unwrap();
+
+        let json = serde_json::to_string(&buffer.to_snapshot()).unwrap();
+        let snapshot: BufferSnapshot = serde_json::from_str(&json).unwrap();
+        let restored = Buffer::from_snapshot(&snapshot).unwrap();
+        let res = restored.lookup_transform("map", "odom", &Stamp::from_nanos(42)).unwrap();
+        assert!(abs_diff_eq!(3.0, res.translation.vector.z));
+    }
+
+}
@@ -0,0 +1,101 @@
+use super::transform_storage::{FrameId, TransformStorage, Stamp};
+use super::time_cache_interface::*;
+
+use TfError::*;
+
+#[derive(Clone)]
+pub struct StaticCache {
+    storage: Option<TransformStorage>
+}
+
+impl TimeCacheInterface for StaticCache {
+
+    fn get_data(&self, stamp: &Stamp) -> Result<TransformStorage, TfError> {
+        match self.storage {
+            Some(ref ts) => {
+                let mut data = ts.clone();
+                data.stamp = stamp.clone();
+                Ok(data)
+            },
+            None => Err(TransformNotFound)
+        }
+    }
+
+    fn get_parent(&self, _stamp: &Stamp) -> Result<FrameId, TfError> {
+        match self.storage {
+            Some(ref ts) => Ok(ts.frame_id),
+            None => Err(NoParent)
+        }
+    }
+
+    fn insert_data(&mut self, new_ts: TransformStorage) -> bool {
+        self.storage = Some(new_ts);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.storage = None;
+    }
+
+    fn get_latest_time_and_parent(&self) -> Option<(Stamp, FrameId)> {
+        let ts = self.storage.as_ref()?;
+        Some((Stamp::from_nanos(0), ts.frame_id))
+    }
+
+    fn get_length(&self) -> usize {
+        if self.storage.is_some() { 1 } else { 0 }
+    }
+
+    fn get_latest_timestamp(&self) -> Option<Stamp> {
+        self.storage.as_ref()?;
+        Some(Stamp::from_nanos(0))
+    }
+
+    fn get_oldest_timestamp(&self) -> Option<Stamp> {
+        self.storage.as_ref()?;
+        Some(Stamp::from_nanos(0))
+    }
+
+    fn get_all_data(&self) -> Vec<TransformStorage> {
+        self.storage.iter().cloned().collect()
+    }
+
+    fn clone_cache(&self) -> Box<dyn TimeCacheInterface> {
+        Box::new(self.clone())
+    }
+
+}
+
+impl StaticCache {
+
+    pub fn new() -> StaticCache {
+        StaticCache {
+            storage: None
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{NTranslation3, NQuaternion, NVector3};
+
+    #[test]
+    fn test_static_get_data_any_time() {
+        let mut cache = StaticCache::new();
+        cache.insert_data(TransformStorage {
+            frame_id       : 1u32,
+            child_frame_id : 2u32,
+            translation    : NTranslation3::new(1.0, 2.0, 3.0),
+            rotation       : NQuaternion::new(NVector3::z()),
+            stamp          : Stamp::from_nanos(100)
+        });
+
+        let ts = cache.get_data(&Stamp::from_nanos(12345)).unwrap();
+        assert_eq!(Stamp::from_nanos(12345), ts.stamp);
+        assert!(abs_diff_eq!(2.0, ts.translation.vector.y));
+        assert_eq!(1u32, cache.get_parent(&Stamp::from_nanos(1)).unwrap());
+    }
+
+}
@@ -0,0 +1,203 @@
+use super::transform_storage::{Stamp, NTranslation3, NQuaternion};
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::Transform;
+
+use nalgebra::Quaternion;
+use serde_yaml::Value;
+
+use std::path::Path;
+
+pub const USAGE: &str = "static_transform_publisher x y z yaw pitch roll frame_id child_frame_id
+static_transform_publisher x y z qx qy qz qw frame_id child_frame_id
+static_transform_publisher --file frames.yaml";
+
+fn invalid(description: String) -> TfError {
+    TfError::InvalidArgument(description)
+}
+
+fn parse_number(name: &str, text: &str) -> Result<f64, TfError> {
+    let value = text.parse::<f64>().map_err(|_| invalid(format!("{} '{}' is not a number", name, text)))?;
+    if !value.is_finite() {
+        return Err(invalid(format!("{} must be finite, got {}", name, text)));
+    }
+    Ok(value)
+}
+
+fn make_rotation(x: f64, y: f64, z: f64, w: f64) -> Result<NQuaternion, TfError> {
+    let q = Quaternion::new(w, x, y, z);
+    if q.norm() < 1.0e-6 {
+        return Err(invalid("the quaternion has zero length".to_string()));
+    }
+    Ok(NQuaternion::from_quaternion(q))
+}
+
+pub fn validate(transform: &Transform) -> Result<(), TfError> {
+    let frame_id = transform.frame_id.trim_start_matches('/');
+    let child_frame_id = transform.child_frame_id.trim_start_matches('/');
+    if frame_id.is_empty() || child_frame_id.is_empty() {
+        return Err(invalid("frame_id and child_frame_id must not be empty".to_string()));
+    }
+    if frame_id == child_frame_id {
+        return Err(invalid(format!("frame_id and child_frame_id are both '{}'", frame_id)));
+    }
+    let v = &transform.translation.vector;
+    let q = &transform.rotation.coords;
+    if !(v.x.is_finite() && v.y.is_finite() && v.z.is_finite() && q.iter().all(|x| x.is_finite())) {
+        return Err(invalid(format!("transform to '{}' is not finite", child_frame_id)));
+    }
+    Ok(())
+}
+
+// Accepts the same positional arguments as the C++ tool, with the rotation
+// given either as yaw pitch roll or as a quaternion
+pub fn parse_args<S: AsRef<str>>(args: &[S]) -> Result<Transform, TfError> {
+    let args: Vec<&str> = args.iter().map(|x| x.as_ref()).collect();
+    let (numbers, frames) = match args.len() {
+        8 => args.split_at(6),
+        9 => args.split_at(7),
+        n => return Err(invalid(format!("expected 8 or 9 arguments, got {}\nusage:\n{}", n, USAGE)))
+    };
+    let names: &[&str] = if numbers.len() == 6 {
+        &["x", "y", "z", "yaw", "pitch", "roll"]
+    } else {
+        &["x", "y", "z", "qx", "qy", "qz", "qw"]
+    };
+    let values = numbers.iter().zip(names.iter())
+        .map(|(text, name)| parse_number(name, text))
+        .collect::<Result<Vec<f64>, TfError>>()?;
+
+    let rotation = if values.len() == 6 {
+        NQuaternion::from_euler_angles(values[5], values[4], values[3])
+    } else {
+        make_rotation(values[3], values[4], values[5], values[6])?
+    };
+    let transform = Transform {
+        frame_id       : frames[0].to_string(),
+        child_frame_id : frames[1].to_string(),
+        translation    : NTranslation3::new(values[0], values[1], values[2]),
+        rotation,
+        stamp          : Stamp::from_nanos(0)
+    };
+    validate(&transform)?;
+    Ok(transform)
+}
+
+fn yaml_numbers(entry: &Value, key: &str, len: usize, index: usize) -> Result<Option<Vec<f64>>, TfError> {
+    let value = match entry.get(key) {
+        Some(value) => value,
+        None => return Ok(None)
+    };
+    let numbers = value.as_sequence()
+        .map(|x| x.iter().filter_map(|v| v.as_f64()).collect::<Vec<f64>>())
+        .filter(|x| x.len() == len)
+        .ok_or_else(|| invalid(format!("transforms[{}].{} must be a list of {} numbers", index, key, len)))?;
+    Ok(Some(numbers))
+}
+
+fn yaml_transform(entry: &Value, index: usize) -> Result<Transform, TfError> {
+    let frame = |key: &str| -> Result<String, TfError> {
+        entry.get(key)
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_string())
+            .ok_or_else(|| invalid(format!("transforms[{}] has no {}", index, key)))
+    };
+    let translation = yaml_numbers(entry, "translation", 3, index)?.unwrap_or_else(|| vec![0.0; 3]);
+    let rotation = match (yaml_numbers(entry, "rotation", 4, index)?, yaml_numbers(entry, "rpy", 3, index)?) {
+        (Some(_), Some(_)) => return Err(invalid(format!("transforms[{}] has both rotation and rpy", index))),
+        (Some(q), None) => make_rotation(q[0], q[1], q[2], q[3])?,
+        (None, Some(rpy)) => NQuaternion::from_euler_angles(rpy[0], rpy[1], rpy[2]),
+        (None, None) => NQuaternion::identity()
+    };
+    let transform = Transform {
+        frame_id       : frame("frame_id")?,
+        child_frame_id : frame("child_frame_id")?,
+        translation    : NTranslation3::new(translation[0], translation[1], translation[2]),
+        rotation,
+        stamp          : Stamp::from_nanos(0)
+    };
+    validate(&transform)?;
+    Ok(transform)
+}
+
+// transforms:
+//   - frame_id: base_link
+//     child_frame_id: laser
+//     translation: [0.1, 0.0, 0.3]
+//     rotation: [0.0, 0.0, 0.0, 1.0]   # or rpy: [roll, pitch, yaw]
+pub fn parse_yaml(text: &str) -> Result<Vec<Transform>, TfError> {
+    let document: Value = serde_yaml::from_str(text)
+        .map_err(|err| TfError::Decode(format!("invalid yaml: {}", err)))?;
+    let entries = document.get("transforms")
+        .and_then(|x| x.as_sequence())
+        .ok_or_else(|| invalid("the yaml file has no transforms list".to_string()))?;
+    let transforms = entries.iter().enumerate()
+        .map(|(index, entry)| yaml_transform(entry, index))
+        .collect::<Result<Vec<Transform>, TfError>>()?;
+
+    for (index, transform) in transforms.iter().enumerate() {
+        if transforms[..index].iter().any(|x| x.child_frame_id == transform.child_frame_id) {
+            return Err(invalid(format!("'{}' is given more than one parent", transform.child_frame_id)));
+        }
+    }
+    Ok(transforms)
+}
+
+pub fn load_yaml_file<P: AsRef<Path>>(path: P) -> Result<Vec<Transform>, TfError> {
+    parse_yaml(&std::fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args_ypr() {
+        let transform = parse_args(&["1", "2", "3", "1.5707963267948966", "0", "0", "base_link", "laser"]).unwrap();
+        assert_eq!("base_link", transform.frame_id);
+        assert_eq!("laser", transform.child_frame_id);
+        assert!(abs_diff_eq!(3.0, transform.translation.vector.z));
+        let (_, _, yaw) = transform.rotation.euler_angles();
+        assert!(abs_diff_eq!(std::f64::consts::FRAC_PI_2, yaw, epsilon = 1.0e-9));
+    }
+
+    #[test]
+    fn test_parse_args_quaternion() {
+        let transform = parse_args(&["0", "0", "0", "0", "0", "0", "2", "map", "odom"]).unwrap();
+        assert!(abs_diff_eq!(1.0, transform.rotation.coords.w));
+    }
+
+    #[test]
+    fn test_parse_args_invalid() {
+        assert!(parse_args(&["0", "0", "0", "0", "0", "0", "map"]).is_err());
+        assert!(parse_args(&["0", "0", "x", "0", "0", "0", "map", "odom"]).is_err());
+        assert!(parse_args(&["0", "0", "0", "0", "0", "0", "map", "map"]).is_err());
+        assert!(parse_args(&["0", "0", "0", "0", "0", "0", "0", "map", "odom"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let transforms = parse_yaml("
+transforms:
+  - frame_id: base_link
+    child_frame_id: laser
+    translation: [0.1, 0.0, 0.3]
+  - frame_id: base_link
+    child_frame_id: camera
+    rpy: [0.0, 0.0, 3.141592653589793]
+").unwrap();
+        assert_eq!(2, transforms.len());
+        assert!(abs_diff_eq!(0.3, transforms[0].translation.vector.z));
+        assert!(abs_diff_eq!(0.0, transforms[1].rotation.coords.w, epsilon = 1.0e-9));
+    }
+
+    #[test]
+    fn test_parse_yaml_two_parents() {
+        let res = parse_yaml("
+transforms:
+  - {frame_id: a, child_frame_id: c}
+  - {frame_id: b, child_frame_id: c}
+");
+        assert!(res.is_err());
+    }
+
+}
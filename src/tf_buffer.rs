@@ -1,32 +1,1101 @@
-
 pub mod tf {
 
-use rosrust::Time;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nalgebra::Isometry3;
+
+use super::super::transform_storage::{FrameId as CompactFrameId, TransformStorage, Stamp,
+                                      NTranslation3, NQuaternion};
+use super::super::time_cache_interface::{TimeCacheInterface, TimeCache, TfError};
+use super::super::static_cache::StaticCache;
+use super::super::clock::{Clock, WallClock};
+use super::super::async_lookup::TransformWaiters;
+use super::super::frame_remapping::FrameRemapping;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+    // same limit as tf2, guards against loops in the frame graph
+    const MAX_GRAPH_DEPTH: usize = 1000;
 
+    const WAIT_POLL_PERIOD: std::time::Duration = std::time::Duration::from_millis(10);
+
+    #[derive(Debug, Clone, PartialEq)]
     pub enum InvalidFrameIdDescription {
+        Empty,
         InvalidCharacters,
         InvalidPrefix,
     }
 
+    impl std::fmt::Display for InvalidFrameIdDescription {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                InvalidFrameIdDescription::Empty => write!(f, "frame id is empty"),
+                InvalidFrameIdDescription::InvalidCharacters => write!(f, "only letters, digits, '_', '-', '.' and '/' are allowed"),
+                InvalidFrameIdDescription::InvalidPrefix => write!(f, "namespaces must not be empty")
+            }
+        }
+    }
+
+    // A frame name as the buffer stores it, without the leading '/' of tf1
+    #[derive(Debug, Clone, PartialEq)]
     pub struct FrameId {
         pub name: String
     }
 
     impl FrameId {
         pub fn new<S: Into<String>>(name: S) -> Result<FrameId, InvalidFrameIdDescription> {
-            Ok(FrameId{name: name.into()})
+            let name = name.into();
+            let name = strip_slash(&name);
+            if name.is_empty() {
+                return Err(InvalidFrameIdDescription::Empty);
+            }
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || "_-./".contains(c)) {
+                return Err(InvalidFrameIdDescription::InvalidCharacters);
+            }
+            if name.split('/').any(|segment| segment.is_empty()) {
+                return Err(InvalidFrameIdDescription::InvalidPrefix);
+            }
+            Ok(FrameId{name: name.to_string()})
         }
     }
 
-    struct Transform {
-        from: FrameId,
-        to: FrameId,
-        stamp: Time,
+    #[derive(Debug, Clone)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct Transform {
+        pub frame_id       : String,
+        pub child_frame_id : String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::translation_xyz"))]
+        pub translation    : NTranslation3,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::rotation_xyzw"))]
+        pub rotation       : NQuaternion,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::stamp_nanos"))]
+        pub stamp          : Stamp
+    }
+
+    // the history of one frame, keyed by name rather than by compact id
+    // so that it stays valid outside the buffer it was taken from
+    #[derive(Debug, Clone)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct FrameSnapshot {
+        pub child_frame_id : String,
+        pub is_static      : bool,
+        pub transforms     : Vec<Transform>
+    }
+
+    #[derive(Debug, Clone)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct BufferSnapshot {
+        pub frames : Vec<FrameSnapshot>
+    }
+
+    // what tf2 reports for each frame in allFramesAsYAML, plus the broadcaster
+    #[derive(Debug, Clone)]
+    pub struct FrameStatistics {
+        pub child_frame_id : String,
+        pub parent         : String,
+        pub authority      : String,
+        pub is_static      : bool,
+        pub length         : usize,
+        // average rate in Hz, zero with less than two samples
+        pub rate           : f64,
+        pub most_recent    : Stamp,
+        pub oldest         : Stamp
+    }
+
+    impl FrameStatistics {
+
+        pub fn buffer_length(&self) -> f64 {
+            (self.most_recent.nanos() - self.oldest.nanos()) as f64 * 1.0e-9
+        }
+
+        // how far behind `current_time` the newest sample is
+        pub fn delay(&self, current_time: &Stamp) -> f64 {
+            if self.is_static {
+                0.0
+            } else {
+                (current_time.nanos() - self.most_recent.nanos()) as f64 * 1.0e-9
+            }
+        }
 
     }
 
-    struct Buffer {
+    const DEFAULT_AUTHORITY: &str = "default_authority";
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum TimeJumpSource {
+        Clock,
+        Stamp
+    }
 
+    // sent to the reset callbacks after the buffer dropped its history
+    #[derive(Debug, Clone)]
+    pub struct TimeJump {
+        pub source   : TimeJumpSource,
+        pub previous : Stamp,
+        pub current  : Stamp
     }
 
+    pub type ResetCallback = Box<dyn Fn(&TimeJump) + Send + Sync>;
+
+    // builds the cache of each new dynamic frame
+    pub type CacheFactory = Arc<dyn Fn() -> Box<dyn TimeCacheInterface> + Send + Sync>;
+
+    // called with the id and name of every frame the buffer interns
+    pub type FrameCallback = Box<dyn Fn(CompactFrameId, &str) + Send + Sync>;
+
+    // Anything that can answer lookups: a local buffer, a listener or a
+    // client of a remote buffer server
+    pub trait TransformLookup {
+        fn lookup_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> Result<Transform, TfError>;
+
+        fn can_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> bool {
+            self.lookup_transform(target_frame, source_frame, time).is_ok()
+        }
+    }
+
+    // caches are shared with views and copied on the first write after one
+    #[derive(Clone)]
+    struct FrameCache {
+        cache        : Arc<dyn TimeCacheInterface>,
+        is_static    : bool,
+        // the broadcaster of the last sample
+        authority    : String,
+        // newest stamp since the last reset, to detect time jumps
+        latest_stamp : Option<Stamp>
+    }
+
+    impl FrameCache {
+        fn cache_mut(&mut self) -> &mut dyn TimeCacheInterface {
+            if Arc::get_mut(&mut self.cache).is_none() {
+                self.cache = Arc::from(self.cache.clone_cache());
+            }
+            Arc::get_mut(&mut self.cache).unwrap()
+        }
+    }
+
+    pub struct Buffer {
+        frame_ids   : HashMap<String, CompactFrameId>,
+        // index 0 is reserved for "no frame", like in tf2
+        frame_names : Vec<String>,
+        frames      : Vec<Option<FrameCache>>,
+        clock       : Arc<dyn Clock>,
+        // samples older than now - cache_time are pruned, None keeps everything
+        cache_time  : Option<Stamp>,
+        // how far back the clock or the stamps of a frame may go before the
+        // buffer resets itself, None disables the detection
+        time_jump_threshold  : Option<Stamp>,
+        last_clock_time      : Stamp,
+        reset_callbacks      : Vec<ResetCallback>,
+        waiters              : Arc<TransformWaiters>,
+        // applied to incoming names before they are interned
+        remapping            : FrameRemapping,
+        // None uses a TimeCache
+        cache_factory        : Option<CacheFactory>,
+        // None uses a StaticCache
+        static_cache_factory : Option<CacheFactory>,
+        frame_callbacks      : Vec<FrameCallback>
+    }
+
+    impl Buffer {
+
+        pub fn new() -> Buffer {
+            Buffer::with_clock(Arc::new(WallClock), None)
+        }
+
+        pub fn with_clock(clock: Arc<dyn Clock>, cache_time: Option<Stamp>) -> Buffer {
+            Buffer {
+                frame_ids   : HashMap::new(),
+                frame_names : vec![String::new()],
+                frames      : vec![None],
+                clock,
+                cache_time,
+                time_jump_threshold  : None,
+                last_clock_time      : Stamp::from_nanos(0),
+                reset_callbacks      : Vec::new(),
+                waiters              : Arc::new(TransformWaiters::new()),
+                remapping            : FrameRemapping::new(),
+                cache_factory        : None,
+                static_cache_factory : None,
+                frame_callbacks      : Vec::new()
+            }
+        }
+
+        pub fn set_time_jump_threshold(&mut self, threshold: Option<Stamp>) {
+            self.time_jump_threshold = threshold;
+            self.last_clock_time = self.clock.now();
+        }
+
+        // only affects transforms inserted from now on
+        pub fn set_remapping(&mut self, remapping: FrameRemapping) {
+            self.remapping = remapping;
+        }
+
+        pub fn get_remapping(&self) -> &FrameRemapping {
+            &self.remapping
+        }
+
+        // e.g. a RingBufferCache for frames read by real-time loops. Only
+        // frames that appear from now on get the new kind of cache.
+        pub fn set_cache_factory(&mut self, factory: Option<CacheFactory>) {
+            self.cache_factory = factory;
+        }
+
+        pub fn set_static_cache_factory(&mut self, factory: Option<CacheFactory>) {
+            self.static_cache_factory = factory;
+        }
+
+        pub fn add_frame_callback(&mut self, callback: FrameCallback) {
+            self.frame_callbacks.push(callback);
+        }
+
+        pub fn add_reset_callback(&mut self, callback: ResetCallback) {
+            self.reset_callbacks.push(callback);
+        }
+
+        // Clears every cache if the clock went backwards, returns whether it did.
+        // Inserts check this too, call it directly when nothing is being inserted.
+        pub fn check_clock_jump(&mut self) -> bool {
+            let threshold = match self.time_jump_threshold {
+                Some(ref threshold) => threshold.nanos(),
+                None => return false
+            };
+            let now = self.clock.now();
+            let previous = std::mem::replace(&mut self.last_clock_time, now.clone());
+            if now.nanos() + threshold < previous.nanos() {
+                self.reset(TimeJump { source: TimeJumpSource::Clock, previous, current: now });
+                true
+            } else {
+                false
+            }
+        }
+
+        // Only a frame going back in time is a jump, publishers that lag
+        // behind the others are not
+        fn check_stamp_jump(&mut self, child: CompactFrameId, stamp: &Stamp) {
+            let threshold = match self.time_jump_threshold {
+                Some(ref threshold) => threshold.nanos(),
+                None => return
+            };
+            let latest = match self.frames[child as usize] {
+                Some(ref frame) => frame.latest_stamp.clone(),
+                None => return
+            };
+            match latest {
+                Some(latest) if stamp.nanos() + threshold < latest.nanos() =>
+                    self.reset(TimeJump { source: TimeJumpSource::Stamp, previous: latest, current: stamp.clone() }),
+                Some(ref latest) if stamp <= latest => return,
+                _ => {}
+            }
+            self.frames[child as usize].as_mut().unwrap().latest_stamp = Some(stamp.clone());
+        }
+
+        // Like tf2, static transforms survive: their publishers latch them
+        // once and would never send them again.
+        fn reset(&mut self, jump: TimeJump) {
+            self.clear_caches(false);
+            for callback in self.reset_callbacks.iter() {
+                callback(&jump);
+            }
+        }
+
+        pub fn now(&self) -> Stamp {
+            self.clock.now()
+        }
+
+        pub fn get_clock(&self) -> Arc<dyn Clock> {
+            self.clock.clone()
+        }
+
+        pub fn set_transform(&mut self, transform: &Transform, is_static: bool) -> Result<(), TfError> {
+            self.set_transform_with_authority(transform, DEFAULT_AUTHORITY, is_static)
+        }
+
+        pub fn set_transform_with_authority(&mut self, transform: &Transform, authority: &str, is_static: bool) -> Result<(), TfError> {
+            let frame_id = &self.remapping.apply(&transform.frame_id);
+            let child_frame_id = &self.remapping.apply(&transform.child_frame_id);
+            for name in [frame_id, child_frame_id].iter() {
+                FrameId::new(name.as_str())
+                    .map_err(|err| TfError::InvalidArgument(format!("invalid frame id \"{}\": {}", name, err)))?;
+            }
+            if frame_id == child_frame_id {
+                return Err(TfError::InvalidArgument(format!("frame_id and child_frame_id are both {}", frame_id)));
+            }
+
+            self.check_clock_jump();
+
+            let prune_before = self.cache_time.as_ref()
+                .map(|cache_time| Stamp::from_nanos(self.clock.now().nanos() - cache_time.nanos()));
+            let parent = self.intern_frame(frame_id);
+            let child = self.intern_frame(child_frame_id);
+            if self.frames[child as usize].is_none() {
+                let cache = self.new_cache(is_static);
+                self.frames[child as usize] = Some(FrameCache { cache, is_static, authority: String::new(), latest_stamp: None });
+            }
+            if !is_static {
+                self.check_stamp_jump(child, &transform.stamp);
+            }
+            let frame = self.frames[child as usize].as_mut().unwrap();
+            let inserted = frame.cache_mut().insert_data(TransformStorage {
+                frame_id       : parent,
+                child_frame_id : child,
+                translation    : transform.translation,
+                rotation       : transform.rotation,
+                stamp          : transform.stamp.clone()
+            });
+            // e.g. older than what a ring buffer or tiered cache keeps
+            if !inserted {
+                return Err(TfError::OldData(format!(
+                    "the sample of {} at {:.9} s was rejected by its cache, it is older than the samples kept",
+                    child_frame_id, transform.stamp.nanos() as f64 * 1.0e-9)));
+            }
+            if frame.authority != authority {
+                frame.authority = authority.to_string();
+            }
+            if let Some(prune_before) = prune_before {
+                frame.cache_mut().prune_older_than(&prune_before);
+            }
+            self.waiters.notify(child_frame_id, &transform.stamp, || self.get_chain_frame_names(frame_id));
+            Ok(())
+        }
+
+        pub fn get_waiters(&self) -> Arc<TransformWaiters> {
+            self.waiters.clone()
+        }
+
+        // `frame` and its known ancestors, following the latest parents
+        pub fn get_chain_frame_names(&self, frame: &str) -> Vec<String> {
+            match self.get_frame_number(frame) {
+                Some(id) => self.latest_chain(id).into_iter().map(|x| self.frame_names[x as usize].clone()).collect(),
+                None => vec![strip_slash(frame).to_string()]
+            }
+        }
+
+        // The newest time every dynamic link between the two frames has data
+        // for, what tf2 looks up at time zero. Zero if the links are all static.
+        pub fn get_latest_common_time(&self, target_frame: &str, source_frame: &str) -> Result<Stamp, TfError> {
+            let target_chain = self.latest_chain(self.lookup_frame_number(target_frame)?);
+            let source_chain = self.latest_chain(self.lookup_frame_number(source_frame)?);
+            let ancestor = *source_chain.iter().find(|x| target_chain.contains(x))
+                .ok_or_else(|| TfError::ConnectivityError(format!(
+                    "could not find a connection between '{}' and '{}' because they are not part of the same tree",
+                    target_frame, source_frame)))?;
+            let links = source_chain.iter().take_while(|x| **x != ancestor)
+                .chain(target_chain.iter().take_while(|x| **x != ancestor));
+            let latest = links
+                .filter_map(|x| self.frames[*x as usize].as_ref())
+                .filter(|x| !x.is_static)
+                .filter_map(|x| x.cache.get_latest_timestamp())
+                .min_by_key(|x| x.nanos());
+            Ok(latest.unwrap_or_else(|| Stamp::from_nanos(0)))
+        }
+
+        fn latest_chain(&self, frame: CompactFrameId) -> Vec<CompactFrameId> {
+            let mut chain = vec![frame];
+            let mut current = frame;
+            while chain.len() < MAX_GRAPH_DEPTH {
+                let parent = match self.get_cache(current).and_then(|x| x.get_latest_time_and_parent()) {
+                    Some((_, parent)) => parent,
+                    None => break
+                };
+                chain.push(parent);
+                current = parent;
+            }
+            chain
+        }
+
+        pub fn lookup_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> Result<Transform, TfError> {
+            let target = self.lookup_frame_number(target_frame)?;
+            let source = self.lookup_frame_number(source_frame)?;
+
+            // like tf2's walkToTopParent, a link above the common ancestor
+            // that cannot answer at `time` does not fail the lookup
+            let (source_chain, source_error) = self.chain_to_root(source, time)?;
+            let (target_chain, target_error) = self.chain_to_root(target, time)?;
+
+            for &(source_ancestor, ref ancestor_from_source) in source_chain.iter() {
+                if let Some(&(_, ref ancestor_from_target)) = target_chain.iter().find(|x| x.0 == source_ancestor) {
+                    let target_from_source = ancestor_from_target.inverse() * ancestor_from_source;
+                    return Ok(Transform {
+                        frame_id       : self.frame_names[target as usize].clone(),
+                        child_frame_id : self.frame_names[source as usize].clone(),
+                        translation    : target_from_source.translation,
+                        rotation       : target_from_source.rotation,
+                        stamp          : time.clone()
+                    });
+                }
+            }
+
+            match source_error.or(target_error) {
+                Some(err) => Err(err),
+                None => Err(TfError::ConnectivityError(format!(
+                    "could not find a connection between '{}' and '{}' because they are not part of the same tree",
+                    target_frame, source_frame)))
+            }
+        }
+
+        pub fn can_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> bool {
+            self.lookup_transform(target_frame, source_frame, time).is_ok()
+        }
+
+        pub fn clear(&mut self) {
+            self.clear_caches(true);
+        }
+
+        // Swaps in empty caches rather than clearing them, a cache still
+        // shared with a view would otherwise be copied only to be emptied
+        fn clear_caches(&mut self, include_static: bool) {
+            for index in 0..self.frames.len() {
+                let is_static = match self.frames[index] {
+                    Some(ref frame) if include_static || !frame.is_static => frame.is_static,
+                    _ => continue
+                };
+                let cache = self.new_cache(is_static);
+                let frame = self.frames[index].as_mut().unwrap();
+                frame.cache = cache;
+                frame.latest_stamp = None;
+            }
+        }
+
+        fn new_cache(&self, is_static: bool) -> Arc<dyn TimeCacheInterface> {
+            match (is_static, &self.static_cache_factory, &self.cache_factory) {
+                (true, Some(factory), _) | (false, _, Some(factory)) => Arc::from(factory()),
+                (true, None, _) => Arc::new(StaticCache::new()),
+                (false, _, None) => Arc::new(TimeCache::new())
+            }
+        }
+
+        pub fn frame_exists(&self, frame: &str) -> bool {
+            self.frame_ids.contains_key(strip_slash(frame))
+        }
+
+        pub fn get_frame_names(&self) -> Vec<String> {
+            self.frame_names.iter().skip(1).cloned().collect()
+        }
+
+        pub fn get_frame_name(&self, frame: CompactFrameId) -> Option<&str> {
+            if frame == 0 {
+                return None;
+            }
+            self.frame_names.get(frame as usize).map(|x| x.as_str())
+        }
+
+        pub fn get_frame_number(&self, frame: &str) -> Option<CompactFrameId> {
+            self.frame_ids.get(strip_slash(frame)).cloned()
+        }
+
+        pub fn get_cache(&self, frame: CompactFrameId) -> Option<&dyn TimeCacheInterface> {
+            let frame = self.frames.get(frame as usize)?.as_ref()?;
+            Some(frame.cache.as_ref())
+        }
+
+        pub fn is_static(&self, frame: CompactFrameId) -> bool {
+            match self.frames.get(frame as usize) {
+                Some(Some(frame)) => frame.is_static,
+                _ => false
+            }
+        }
+
+        // the full history of every frame, as stored in the caches
+        pub fn get_all_transforms(&self) -> Vec<(Transform, bool)> {
+            let mut transforms = Vec::new();
+            for frame in self.frames.iter() {
+                if let Some(frame) = frame {
+                    for ts in frame.cache.get_all_data() {
+                        transforms.push((self.transform_from_storage(&ts), frame.is_static));
+                    }
+                }
+            }
+            transforms
+        }
+
+        pub fn get_frame_statistics(&self) -> Vec<FrameStatistics> {
+            let mut statistics = Vec::new();
+            for (child, frame) in self.frames.iter().enumerate() {
+                let frame = match frame {
+                    Some(frame) => frame,
+                    None => continue
+                };
+                let (most_recent, parent) = match frame.cache.get_latest_time_and_parent() {
+                    Some(latest) => latest,
+                    None => continue
+                };
+                let oldest = frame.cache.get_oldest_timestamp().unwrap_or_else(|| most_recent.clone());
+                let length = frame.cache.get_length();
+                let span = (most_recent.nanos() - oldest.nanos()) as f64 * 1.0e-9;
+                statistics.push(FrameStatistics {
+                    child_frame_id : self.frame_names[child].clone(),
+                    parent         : self.frame_names[parent as usize].clone(),
+                    authority      : frame.authority.clone(),
+                    is_static      : frame.is_static,
+                    length,
+                    rate           : if length > 1 && span > 0.0 { (length - 1) as f64 / span } else { 0.0 },
+                    most_recent,
+                    oldest
+                });
+            }
+            statistics
+        }
+
+        // Graphviz description of the frame tree, same layout as tf2's allFramesAsDot
+        pub fn all_frames_as_dot(&self, current_time: &Stamp) -> String {
+            let statistics = self.get_frame_statistics();
+            let mut dot = String::from("digraph G {\n");
+            if statistics.is_empty() {
+                dot.push_str("\"no tf data received\"\n");
+            }
+            for stats in statistics.iter() {
+                dot.push_str(&format!(
+                    "\"{}\" -> \"{}\"[label=\"Broadcaster: {}\\nAverage rate: {:.3} Hz\\nMost recent transform: {:.3} ( {:.3} sec old)\\nBuffer length: {:.3} sec\\n\"];\n",
+                    stats.parent, stats.child_frame_id, stats.authority,
+                    stats.rate, stats.most_recent.nanos() as f64 * 1.0e-9,
+                    stats.delay(current_time), stats.buffer_length()));
+            }
+            let roots: Vec<&String> = statistics.iter()
+                .map(|x| &x.parent)
+                .filter(|parent| !statistics.iter().any(|x| &x.child_frame_id == *parent))
+                .collect();
+            for (index, root) in roots.iter().enumerate() {
+                if roots[..index].contains(root) {
+                    continue;
+                }
+                dot.push_str(&format!(
+                    "edge [style=invis];\n subgraph cluster_legend {{ style=bold; color=black; label =\"view_frames Result\";\n\"Recorded at time: {:.3}\"[ shape=plaintext ] ;\n }}->\"{}\";\n",
+                    current_time.nanos() as f64 * 1.0e-9, root));
+            }
+            dot.push_str("}\n");
+            dot
+        }
+
+        // An immutable view of the buffer as it is now. Only O(frames) to
+        // take, since the caches are shared until the buffer writes to them.
+        // to_snapshot copies the history out instead, e.g. to serialize it.
+        pub fn snapshot(&self) -> BufferView {
+            BufferView {
+                buffer : Arc::new(Buffer {
+                    frame_ids            : self.frame_ids.clone(),
+                    frame_names          : self.frame_names.clone(),
+                    frames               : self.frames.clone(),
+                    clock                : self.clock.clone(),
+                    cache_time           : self.cache_time.clone(),
+                    time_jump_threshold  : None,
+                    last_clock_time      : self.last_clock_time.clone(),
+                    reset_callbacks      : Vec::new(),
+                    waiters              : Arc::new(TransformWaiters::new()),
+                    remapping            : self.remapping.clone(),
+                    cache_factory        : self.cache_factory.clone(),
+                    static_cache_factory : self.static_cache_factory.clone(),
+                    frame_callbacks      : Vec::new()
+                })
+            }
+        }
+
+        pub fn to_snapshot(&self) -> BufferSnapshot {
+            let mut frames = Vec::new();
+            for (child, frame) in self.frames.iter().enumerate() {
+                if let Some(frame) = frame {
+                    frames.push(FrameSnapshot {
+                        child_frame_id : self.frame_names[child].clone(),
+                        is_static      : frame.is_static,
+                        transforms     : frame.cache.get_all_data().iter()
+                                             .map(|ts| self.transform_from_storage(ts)).collect()
+                    });
+                }
+            }
+            BufferSnapshot { frames }
+        }
+
+        pub fn from_snapshot(snapshot: &BufferSnapshot) -> Result<Buffer, TfError> {
+            let mut buffer = Buffer::new();
+            for frame in snapshot.frames.iter() {
+                for transform in frame.transforms.iter() {
+                    buffer.set_transform(transform, frame.is_static)?;
+                }
+            }
+            Ok(buffer)
+        }
+
+        // A copy holding only the frames below `root`, e.g. the part of the
+        // tree published by one robot. Parents are taken from the latest samples.
+        pub fn subtree(&self, root: &str) -> Result<Buffer, TfError> {
+            let root = self.lookup_frame_number(root)?;
+            let mut members = vec![false; self.frames.len()];
+            members[root as usize] = true;
+            for frame in 1..self.frames.len() {
+                let mut chain = Vec::new();
+                let mut current = frame as CompactFrameId;
+                while !members[current as usize] && chain.len() < MAX_GRAPH_DEPTH {
+                    chain.push(current);
+                    current = match self.get_cache(current).and_then(|x| x.get_latest_time_and_parent()) {
+                        Some((_, parent)) => parent,
+                        None => break
+                    };
+                }
+                if members[current as usize] {
+                    for id in chain {
+                        members[id as usize] = true;
+                    }
+                }
+            }
+
+            let mut subtree = Buffer::with_clock(self.clock.clone(), self.cache_time.clone());
+            for (child, frame) in self.frames.iter().enumerate() {
+                let frame = match frame {
+                    Some(frame) if child != root as usize && members[child] => frame,
+                    _ => continue
+                };
+                // samples from before the frame joined the subtree are left out
+                for ts in frame.cache.get_all_data().iter().filter(|ts| members[ts.frame_id as usize]) {
+                    subtree.set_transform_with_authority(&self.transform_from_storage(ts), &frame.authority, frame.is_static)?;
+                }
+            }
+            Ok(subtree)
+        }
+
+        fn transform_from_storage(&self, ts: &TransformStorage) -> Transform {
+            Transform {
+                frame_id       : self.frame_names[ts.frame_id as usize].clone(),
+                child_frame_id : self.frame_names[ts.child_frame_id as usize].clone(),
+                translation    : ts.translation,
+                rotation       : ts.rotation,
+                stamp          : ts.stamp.clone()
+            }
+        }
+
+        fn intern_frame(&mut self, frame: &str) -> CompactFrameId {
+            if let Some(id) = self.frame_ids.get(frame) {
+                return *id;
+            }
+            let id = self.frame_names.len() as CompactFrameId;
+            self.frame_ids.insert(frame.to_string(), id);
+            self.frame_names.push(frame.to_string());
+            self.frames.push(None);
+            for callback in self.frame_callbacks.iter() {
+                callback(id, frame);
+            }
+            id
+        }
+
+        fn lookup_frame_number(&self, frame: &str) -> Result<CompactFrameId, TfError> {
+            self.get_frame_number(frame).ok_or_else(||
+                TfError::LookupError(format!("\"{}\" passed to lookup_transform does not exist", frame)))
+        }
+
+        // every frame from `frame` up to its root, each paired with the
+        // transform that maps `frame` coordinates into it. The walk stops
+        // early at a link that has no data at `time`, with its error.
+        fn chain_to_root(&self, frame: CompactFrameId, time: &Stamp)
+                         -> Result<(Vec<(CompactFrameId, Isometry3<f64>)>, Option<TfError>), TfError> {
+            let mut chain = vec![(frame, Isometry3::identity())];
+            let mut current = frame;
+            loop {
+                let cache = match self.frames[current as usize] {
+                    Some(ref frame) => &frame.cache,
+                    None => break
+                };
+                let ts = match cache.get_data(time) {
+                    Ok(ts) => ts,
+                    Err(TfError::TransformNotFound) => break,
+                    Err(err) => return Ok((chain, Some(err)))
+                };
+                let parent_from_current = Isometry3::from_parts(ts.translation, ts.rotation);
+                let parent_from_frame = parent_from_current * chain.last().unwrap().1;
+                chain.push((ts.frame_id, parent_from_frame));
+                current = ts.frame_id;
+
+                if chain.len() > MAX_GRAPH_DEPTH {
+                    return Err(TfError::LookupError(format!(
+                        "the tf tree is invalid because it contains a loop through '{}'",
+                        self.frame_names[frame as usize])));
+                }
+            }
+            Ok((chain, None))
+        }
+
+    }
+
+    // Returned by Buffer::snapshot, clones share the same frozen buffer.
+    // Only the read-only part of the Buffer API is reachable through it.
+    #[derive(Clone)]
+    pub struct BufferView {
+        buffer : Arc<Buffer>
+    }
+
+    impl std::ops::Deref for BufferView {
+        type Target = Buffer;
+
+        fn deref(&self) -> &Buffer {
+            &self.buffer
+        }
+    }
+
+    impl TransformLookup for BufferView {
+        fn lookup_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> Result<Transform, TfError> {
+            self.buffer.lookup_transform(target_frame, source_frame, time)
+        }
+    }
+
+    impl TransformLookup for Buffer {
+        fn lookup_transform(&self, target_frame: &str, source_frame: &str, time: &Stamp) -> Result<Transform, TfError> {
+            Buffer::lookup_transform(self, target_frame, source_frame, time)
+        }
+    }
+
+    // Polls the buffer until the transform can be computed, giving up once
+    // `timeout` has passed on the buffer's clock
+    pub fn wait_for_transform(buffer: &RwLock<Buffer>, target_frame: &str, source_frame: &str,
+                              time: &Stamp, timeout: &Stamp) -> Result<Transform, TfError> {
+        wait_for_transform_cancellable(buffer, target_frame, source_frame, time, timeout, &AtomicBool::new(false)).unwrap()
+    }
+
+    // Same as wait_for_transform, but None as soon as `cancelled` is set
+    pub fn wait_for_transform_cancellable(buffer: &RwLock<Buffer>, target_frame: &str, source_frame: &str,
+                                          time: &Stamp, timeout: &Stamp, cancelled: &AtomicBool) -> Option<Result<Transform, TfError>> {
+        let deadline = buffer.read().unwrap().now().nanos() + timeout.nanos();
+        loop {
+            let (res, now) = {
+                let buffer = buffer.read().unwrap();
+                (buffer.lookup_transform(target_frame, source_frame, time), buffer.now())
+            };
+            match res {
+                Ok(transform) => return Some(Ok(transform)),
+                Err(err) => {
+                    if now.nanos() >= deadline {
+                        return Some(Err(err));
+                    }
+                }
+            }
+            if cancelled.load(Ordering::SeqCst) {
+                return None;
+            }
+            std::thread::sleep(WAIT_POLL_PERIOD);
+        }
+    }
+
+    fn strip_slash(frame: &str) -> &str {
+        if frame.starts_with('/') { &frame[1..] } else { frame }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::transform_storage::NVector3;
+    use super::super::super::clock::ManualClock;
+    use super::super::super::ring_buffer_cache::RingBufferCache;
+
+    fn make_transform(parent: &str, child: &str, x: f64, y: f64, nanos: i64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : NTranslation3::new(x, y, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    #[test]
+    fn test_lookup_chain() {
+        let mut buffer = Buffer::new();
+        buffer.set_transform(&make_transform("map", "odom", 1.0, 0.0, 100), false).unwrap();
+        buffer.set_transform(&make_transform("map", "odom", 3.0, 0.0, 200), false).unwrap();
+        buffer.set_transform(&make_transform("odom", "base_link", 0.0, 2.0, 100), false).unwrap();
+        buffer.set_transform(&make_transform("odom", "base_link", 0.0, 4.0, 200), false).unwrap();
+
+        let res = buffer.lookup_transform("map", "base_link", &Stamp::from_nanos(150)).unwrap();
+        assert_eq!("map", res.frame_id);
+        assert_eq!("base_link", res.child_frame_id);
+        assert!(abs_diff_eq!(2.0, res.translation.vector.x));
+        assert!(abs_diff_eq!(3.0, res.translation.vector.y));
+
+        let inverse = buffer.lookup_transform("base_link", "map", &Stamp::from_nanos(150)).unwrap();
+        assert!(abs_diff_eq!(-2.0, inverse.translation.vector.x));
+        assert!(abs_diff_eq!(-3.0, inverse.translation.vector.y));
+    }
+
+    #[test]
+    fn test_lookup_siblings_with_rotation() {
+        let mut buffer = Buffer::new();
+        let mut laser = make_transform("base_link", "laser", 1.0, 0.0, 100);
+        laser.rotation = NQuaternion::from_axis_angle(&NVector3::z_axis(), std::f64::consts::FRAC_PI_2);
+        buffer.set_transform(&laser, true).unwrap();
+        buffer.set_transform(&make_transform("base_link", "camera", 0.0, 1.0, 100), true).unwrap();
+
+        let res = buffer.lookup_transform("laser", "camera", &Stamp::from_nanos(0)).unwrap();
+        assert!(abs_diff_eq!(1.0, res.translation.vector.x, epsilon = 1.0e-9));
+        assert!(abs_diff_eq!(1.0, res.translation.vector.y, epsilon = 1.0e-9));
+    }
+
+    #[test]
+    fn test_lookup_not_connected() {
+        let mut buffer = Buffer::new();
+        buffer.set_transform(&make_transform("map", "odom", 1.0, 0.0, 100), true).unwrap();
+        buffer.set_transform(&make_transform("world", "robot", 1.0, 0.0, 100), true).unwrap();
+
+        match buffer.lookup_transform("map", "robot", &Stamp::from_nanos(0)) {
+            Err(TfError::ConnectivityError(_)) => {},
+            res => assert!(false, "result {:?} was not expected", res)
+        }
+        assert!(!buffer.can_transform("map", "unknown", &Stamp::from_nanos(0)));
+    }
+
+    #[test]
+    fn test_lookup_ignores_links_above_common_ancestor() {
+        let mut buffer = Buffer::new();
+        buffer.set_transform(&make_transform("map", "odom", 1.0, 0.0, 100), false).unwrap();
+        buffer.set_transform(&make_transform("map", "odom", 2.0, 0.0, 200), false).unwrap();
+        buffer.set_transform(&make_transform("odom", "base_link", 1.0, 0.0, 100), false).unwrap();
+        buffer.set_transform(&make_transform("odom", "base_link", 5.0, 0.0, 500), false).unwrap();
+        buffer.set_transform(&make_transform("base_link", "laser", 0.5, 0.0, 0), true).unwrap();
+
+        // map -> odom has no data at 400, but neither lookup goes through it
+        let res = buffer.lookup_transform("base_link", "laser", &Stamp::from_nanos(400)).unwrap();
+        assert!(abs_diff_eq!(0.5, res.translation.vector.x));
+        let res = buffer.lookup_transform("odom", "laser", &Stamp::from_nanos(400)).unwrap();
+        assert!(abs_diff_eq!(4.5, res.translation.vector.x, epsilon = 1.0e-9));
+        match buffer.lookup_transform("map", "laser", &Stamp::from_nanos(400)) {
+            Err(TfError::ExtrapolationError1) | Err(TfError::ExtrapolationError2) |
+            Err(TfError::ExtrapolationError3) | Err(TfError::ExtrapolationError(_)) => {},
+            res => assert!(false, "result {:?} was not expected", res)
+        }
+    }
+
+    #[test]
+    fn test_set_invalid_frame_ids() {
+        let mut buffer = Buffer::new();
+        for (parent, child) in [("", "odom"), ("map", "/"), ("map", "map"), ("/map", "map"),
+                                ("map", "base link"), ("robot1//odom", "base_link")].iter() {
+            match buffer.set_transform(&make_transform(parent, child, 1.0, 0.0, 100), false) {
+                Err(TfError::InvalidArgument(_)) => {},
+                res => assert!(false, "result {:?} was not expected", res)
+            }
+        }
+        assert!(buffer.get_frame_names().is_empty());
+    }
+
+    #[test]
+    fn test_prune_with_clock() {
+        let clock = Arc::new(ManualClock::new(Stamp::from_nanos(1000)));
+        let mut buffer = Buffer::with_clock(clock.clone(), Some(Stamp::from_nanos(500)));
+        buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 400), false).unwrap();
+        buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 600), false).unwrap();
+        buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 800), false).unwrap();
+
+        let odom = buffer.get_frame_number("odom").unwrap();
+        assert_eq!(2, buffer.get_cache(odom).unwrap().get_length());
+        assert_eq!(Some(Stamp::from_nanos(600)), buffer.get_cache(odom).unwrap().get_oldest_timestamp());
+    }
+
+    #[test]
+    fn test_wait_for_transform() {
+        let clock = Arc::new(ManualClock::new(Stamp::from_nanos(0)));
+        let buffer = Arc::new(RwLock::new(Buffer::with_clock(clock.clone(), None)));
+
+        let writer = buffer.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            writer.write().unwrap().set_transform(&make_transform("map", "odom", 1.0, 0.0, 0), true).unwrap();
+        });
+        let res = wait_for_transform(&buffer, "map", "odom", &Stamp::from_nanos(0), &Stamp::from_nanos(100));
+        handle.join().unwrap();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_wait_for_transform_timeout() {
+        let clock = Arc::new(ManualClock::new(Stamp::from_nanos(0)));
+        let buffer = RwLock::new(Buffer::with_clock(clock.clone(), None));
+        buffer.write().unwrap().set_transform(&make_transform("map", "odom", 1.0, 0.0, 0), true).unwrap();
+
+        // the timeout only expires when the clock moves
+        let clock_writer = clock.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            clock_writer.set(Stamp::from_nanos(1_000_000_000));
+        });
+        let res = wait_for_transform(&buffer, "map", "base_link", &Stamp::from_nanos(0), &Stamp::from_nanos(100));
+        handle.join().unwrap();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_reset_on_clock_jump() {
+        let clock = Arc::new(ManualClock::new(Stamp::from_nanos(10_000_000_000)));
+        let mut buffer = Buffer::with_clock(clock.clone(), None);
+        buffer.set_time_jump_threshold(Some(Stamp::from_nanos(1_000_000_000)));
+        let jumps = Arc::new(RwLock::new(Vec::new()));
+        let callback_jumps = jumps.clone();
+        buffer.add_reset_callback(Box::new(move |jump: &TimeJump| callback_jumps.write().unwrap().push(jump.clone())));
+
+        buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 10_000_000_000), false).unwrap();
+        // a small step back is tolerated
+        clock.set(Stamp::from_nanos(9_500_000_000));
+        assert!(!buffer.check_clock_jump());
+        clock.set(Stamp::from_nanos(1_000_000_000));
+        buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 1_000_000_000), false).unwrap();
+
+        let odom = buffer.get_frame_number("odom").unwrap();
+        assert_eq!(1, buffer.get_cache(odom).unwrap().get_length());
+        let jumps = jumps.read().unwrap();
+        assert_eq!(1, jumps.len());
+        assert_eq!(TimeJumpSource::Clock, jumps[0].source);
+        assert_eq!(Stamp::from_nanos(1_000_000_000), jumps[0].current);
+    }
+
+    #[test]
+    fn test_reset_on_stamp_jump() {
+        let mut buffer = Buffer::new();
+        buffer.set_time_jump_threshold(Some(Stamp::from_nanos(1_000_000_000)));
+        buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 5_000_000_000), false).unwrap();
+        buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 6_000_000_000), false).unwrap();
+        // slightly out of order data is not a jump
+        buffer.set_transform(&make_transform("odom", "base_link", 0.0, 0.0, 5_500_000_000), false).unwrap();
+        let odom = buffer.get_frame_number("odom").unwrap();
+        assert_eq!(2, buffer.get_cache(odom).unwrap().get_length());
+
+        buffer.set_transform(&make_transform("base_link", "laser", 0.5, 0.0, 0), true).unwrap();
+
+        // a publisher lagging behind the others is not a jump
+        buffer.set_transform(&make_transform("map", "gps", 0.0, 0.0, 3_000_000_000), false).unwrap();
+        assert_eq!(2, buffer.get_cache(odom).unwrap().get_length());
+
+        // the bag looped
+        buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 1_000_000_000), false).unwrap();
+        assert_eq!(1, buffer.get_cache(odom).unwrap().get_length());
+        let base_link = buffer.get_frame_number("base_link").unwrap();
+        assert_eq!(0, buffer.get_cache(base_link).unwrap().get_length());
+
+        // the latched static link is kept
+        buffer.set_transform(&make_transform("odom", "base_link", 1.0, 0.0, 1_000_000_000), false).unwrap();
+        let res = buffer.lookup_transform("map", "laser", &Stamp::from_nanos(1_000_000_000)).unwrap();
+        assert!(abs_diff_eq!(1.5, res.translation.vector.x));
+    }
+
+    #[test]
+    fn test_frame_statistics() {
+        let mut buffer = Buffer::new();
+        for i in 0..11 {
+            buffer.set_transform_with_authority(&make_transform("map", "odom", 0.0, 0.0, i * 100_000_000),
+                                                "/localization", false).unwrap();
+        }
+        buffer.set_transform(&make_transform("odom", "base_link", 0.0, 0.0, 0), true).unwrap();
+
+        let statistics = buffer.get_frame_statistics();
+        assert_eq!(2, statistics.len());
+        let odom = statistics.iter().find(|x| x.child_frame_id == "odom").unwrap();
+        assert_eq!("map", odom.parent);
+        assert_eq!("/localization", odom.authority);
+        assert!(abs_diff_eq!(10.0, odom.rate, epsilon = 1.0e-9));
+        assert!(abs_diff_eq!(1.0, odom.buffer_length(), epsilon = 1.0e-9));
+        assert!(abs_diff_eq!(0.5, odom.delay(&Stamp::from_nanos(1_500_000_000)), epsilon = 1.0e-9));
+
+        let dot = buffer.all_frames_as_dot(&Stamp::from_nanos(1_500_000_000));
+        assert!(dot.contains("\"map\" -> \"odom\""));
+        assert!(dot.contains("\"odom\" -> \"base_link\""));
+        assert!(dot.contains("}->\"map\""));
+    }
+
+    #[test]
+    fn test_frame_id_validation() {
+        assert_eq!("robot1/base_link", FrameId::new("/robot1/base_link").unwrap().name);
+        assert_eq!(Err(InvalidFrameIdDescription::Empty), FrameId::new("/"));
+        assert_eq!(Err(InvalidFrameIdDescription::InvalidCharacters), FrameId::new("base link"));
+        assert_eq!(Err(InvalidFrameIdDescription::InvalidPrefix), FrameId::new("robot1//base_link"));
+    }
+
+    #[test]
+    fn test_remapping_and_subtree() {
+        let mut buffer = Buffer::new();
+        buffer.set_transform(&make_transform("map", "robot1/odom", 1.0, 0.0, 0), true).unwrap();
+        buffer.set_remapping(FrameRemapping::new().with_prefix("robot1").unwrap());
+        buffer.set_transform(&make_transform("odom", "base_link", 2.0, 0.0, 0), true).unwrap();
+        buffer.set_transform(&make_transform("base_link", "laser", 0.0, 1.0, 0), true).unwrap();
+        buffer.set_remapping(FrameRemapping::new().with_prefix("robot2").unwrap());
+        buffer.set_transform(&make_transform("/robot2/odom", "base_link", 3.0, 0.0, 0), true).unwrap();
+
+        assert!(buffer.frame_exists("robot1/base_link"));
+        assert!(!buffer.frame_exists("base_link"));
+        let res = buffer.lookup_transform("map", "robot1/laser", &Stamp::from_nanos(0)).unwrap();
+        assert!(abs_diff_eq!(3.0, res.translation.vector.x));
+
+        let robot1 = buffer.subtree("robot1/odom").unwrap();
+        let mut names = robot1.get_frame_names();
+        names.sort();
+        assert_eq!(vec!["robot1/base_link", "robot1/laser", "robot1/odom"], names);
+        let res = robot1.lookup_transform("robot1/odom", "robot1/laser", &Stamp::from_nanos(0)).unwrap();
+        assert!(abs_diff_eq!(2.0, res.translation.vector.x));
+        assert!(robot1.lookup_transform("map", "robot1/laser", &Stamp::from_nanos(0)).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_ignores_later_writes() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        buffer.write().unwrap().set_transform(&make_transform("map", "odom", 1.0, 0.0, 100), false).unwrap();
+        buffer.write().unwrap().set_transform(&make_transform("odom", "base_link", 2.0, 0.0, 100), false).unwrap();
+        let view = buffer.read().unwrap().snapshot();
+
+        let writer = buffer.clone();
+        std::thread::spawn(move || {
+            let mut writer = writer.write().unwrap();
+            writer.set_transform(&make_transform("map", "odom", 5.0, 0.0, 200), false).unwrap();
+            writer.set_transform(&make_transform("base_link", "laser", 0.0, 1.0, 200), true).unwrap();
+            writer.clear();
+        }).join().unwrap();
+
+        let copy = view.clone();
+        let res = copy.lookup_transform("map", "base_link", &Stamp::from_nanos(0)).unwrap();
+        assert!(abs_diff_eq!(3.0, res.translation.vector.x));
+        assert!(!copy.frame_exists("laser"));
+        assert_eq!(1, copy.get_cache(copy.get_frame_number("odom").unwrap()).unwrap().get_length());
+        assert!(buffer.read().unwrap().lookup_transform("map", "base_link", &Stamp::from_nanos(0)).is_err());
+    }
+
+    #[test]
+    fn test_cache_factory() {
+        let mut buffer = Buffer::new();
+        buffer.set_cache_factory(Some(Arc::new(|| Box::new(RingBufferCache::new(2)) as Box<dyn TimeCacheInterface>)));
+        for i in 1..5 {
+            buffer.set_transform(&make_transform("map", "odom", i as f64, 0.0, i * 100), false).unwrap();
+        }
+        let odom = buffer.get_frame_number("odom").unwrap();
+        assert_eq!(2, buffer.get_cache(odom).unwrap().get_length());
+        let res = buffer.lookup_transform("map", "odom", &Stamp::from_nanos(350)).unwrap();
+        assert!(abs_diff_eq!(3.5, res.translation.vector.x));
+
+        // the ring buffer only takes samples in order, waiters are not woken for a rejected one
+        let waiters = buffer.get_waiters();
+        waiters.register(waiters.next_id(), &["odom".to_string()], &futures::task::noop_waker());
+        match buffer.set_transform(&make_transform("map", "odom", 0.0, 0.0, 50), false) {
+            Err(TfError::OldData(_)) => {},
+            res => assert!(false, "result {:?} was not expected", res)
+        }
+        assert_eq!(1, waiters.pending_frames());
+        assert_eq!(2, buffer.get_cache(odom).unwrap().get_length());
+    }
+
+    #[test]
+    fn test_lookup_follows_reparenting() {
+        let mut buffer = Buffer::new();
+        buffer.set_transform(&make_transform("world", "table", 1.0, 0.0, 0), true).unwrap();
+        buffer.set_transform(&make_transform("world", "gripper", 0.0, 5.0, 0), true).unwrap();
+        buffer.set_transform(&make_transform("table", "cup", 0.0, 1.0, 100), false).unwrap();
+        buffer.set_transform(&make_transform("table", "cup", 0.0, 1.0, 200), false).unwrap();
+        // picked up
+        buffer.set_transform(&make_transform("gripper", "cup", 0.0, 0.5, 300), false).unwrap();
+        buffer.set_transform(&make_transform("gripper", "cup", 0.0, 0.5, 400), false).unwrap();
+
+        let on_table = buffer.lookup_transform("world", "cup", &Stamp::from_nanos(250)).unwrap();
+        assert!(abs_diff_eq!(1.0, on_table.translation.vector.x));
+        assert!(abs_diff_eq!(1.0, on_table.translation.vector.y));
+
+        let in_gripper = buffer.lookup_transform("world", "cup", &Stamp::from_nanos(300)).unwrap();
+        assert!(abs_diff_eq!(0.0, in_gripper.translation.vector.x));
+        assert!(abs_diff_eq!(5.5, in_gripper.translation.vector.y));
+
+        let latest = buffer.lookup_transform("gripper", "cup", &Stamp::from_nanos(0)).unwrap();
+        assert!(abs_diff_eq!(0.5, latest.translation.vector.y));
+    }
+
+}
+
 }
@@ -0,0 +1,280 @@
+use super::transform_storage::{Stamp, NTranslation3, NQuaternion};
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::Transform;
+
+use nalgebra::Quaternion;
+
+pub const TF_MESSAGE_DATATYPE: &str = "tf2_msgs/TFMessage";
+pub const TF_MESSAGE_ROS2_DATATYPE: &str = "tf2_msgs/msg/TFMessage";
+
+pub const TF_MESSAGE_ROS1_DEFINITION: &str = "geometry_msgs/TransformStamped[] transforms
+================================================================================
+MSG: geometry_msgs/TransformStamped
+Header header
+string child_frame_id
+Transform transform
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+================================================================================
+MSG: geometry_msgs/Transform
+Vector3 translation
+Quaternion rotation
+================================================================================
+MSG: geometry_msgs/Vector3
+float64 x
+float64 y
+float64 z
+================================================================================
+MSG: geometry_msgs/Quaternion
+float64 x
+float64 y
+float64 z
+float64 w
+";
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+// CDR_LE encapsulation, the only one ROS 2 publishes by default
+const CDR_LE_HEADER: [u8; 4] = [0x00, 0x01, 0x00, 0x00];
+
+pub fn is_tf_message_datatype(datatype: &str) -> bool {
+    datatype == TF_MESSAGE_DATATYPE || datatype == TF_MESSAGE_ROS2_DATATYPE
+}
+
+struct Reader<'a> {
+    data   : &'a [u8],
+    pos    : usize,
+    // CDR aligns primitives relative to the start of the payload
+    origin : usize,
+    cdr    : bool
+}
+
+impl<'a> Reader<'a> {
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TfError> {
+        if self.pos + len > self.data.len() {
+            return Err(TfError::Decode("TFMessage is truncated".to_string()));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn align(&mut self, size: usize) {
+        if self.cdr {
+            let offset = (self.pos - self.origin) % size;
+            if offset != 0 {
+                self.pos += size - offset;
+            }
+        }
+    }
+
+    fn read_u32(&mut self) -> Result<u32, TfError> {
+        self.align(4);
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, TfError> {
+        self.align(8);
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(buf))
+    }
+
+    fn read_string(&mut self) -> Result<String, TfError> {
+        let len = self.read_u32()? as usize;
+        let mut bytes = self.take(len)?;
+        if self.cdr && bytes.last() == Some(&0) {
+            bytes = &bytes[..len - 1];
+        }
+        String::from_utf8(bytes.to_vec()).map_err(|_| TfError::Decode("frame id is not valid utf-8".to_string()))
+    }
+
+    fn read_transform(&mut self) -> Result<Transform, TfError> {
+        if !self.cdr {
+            // header.seq
+            self.read_u32()?;
+        }
+        let sec = self.read_u32()?;
+        let nsec = self.read_u32()?;
+        let frame_id = self.read_string()?;
+        let child_frame_id = self.read_string()?;
+        let translation = NTranslation3::new(self.read_f64()?, self.read_f64()?, self.read_f64()?);
+        let (x, y, z, w) = (self.read_f64()?, self.read_f64()?, self.read_f64()?, self.read_f64()?);
+        let stamp_nanos = if self.cdr {
+            (sec as i32) as i64 * NANOS_PER_SEC + nsec as i64
+        } else {
+            sec as i64 * NANOS_PER_SEC + nsec as i64
+        };
+        Ok(Transform {
+            frame_id,
+            child_frame_id,
+            translation,
+            rotation : NQuaternion::from_quaternion(Quaternion::new(w, x, y, z)),
+            stamp    : Stamp::from_nanos(stamp_nanos)
+        })
+    }
+
+}
+
+struct Writer {
+    data : Vec<u8>,
+    cdr  : bool
+}
+
+impl Writer {
+
+    fn align(&mut self, size: usize) {
+        if self.cdr {
+            while (self.data.len() - CDR_LE_HEADER.len()) % size != 0 {
+                self.data.push(0);
+            }
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.align(4);
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_f64(&mut self, value: f64) {
+        self.align(8);
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_string(&mut self, value: &str) {
+        if self.cdr {
+            self.write_u32(value.len() as u32 + 1);
+            self.data.extend_from_slice(value.as_bytes());
+            self.data.push(0);
+        } else {
+            self.write_u32(value.len() as u32);
+            self.data.extend_from_slice(value.as_bytes());
+        }
+    }
+
+    fn write_transform(&mut self, transform: &Transform) {
+        let nanos = transform.stamp.nanos();
+        if !self.cdr {
+            self.write_u32(0);
+        }
+        self.write_u32(nanos.div_euclid(NANOS_PER_SEC) as u32);
+        self.write_u32(nanos.rem_euclid(NANOS_PER_SEC) as u32);
+        self.write_string(&transform.frame_id);
+        self.write_string(&transform.child_frame_id);
+        let t = &transform.translation.vector;
+        self.write_f64(t.x);
+        self.write_f64(t.y);
+        self.write_f64(t.z);
+        let q = &transform.rotation.coords;
+        self.write_f64(q.x);
+        self.write_f64(q.y);
+        self.write_f64(q.z);
+        self.write_f64(q.w);
+    }
+
+}
+
+fn decode(data: &[u8], cdr: bool) -> Result<Vec<Transform>, TfError> {
+    let origin = if cdr { CDR_LE_HEADER.len() } else { 0 };
+    if cdr && (data.len() < origin || data[1] != CDR_LE_HEADER[1]) {
+        return Err(TfError::Decode("only little endian CDR is supported".to_string()));
+    }
+    let mut reader = Reader { data, pos: origin, origin, cdr };
+    let count = reader.read_u32()?;
+    let mut transforms = Vec::new();
+    for _ in 0..count {
+        transforms.push(reader.read_transform()?);
+    }
+    Ok(transforms)
+}
+
+fn encode(transforms: &[Transform], cdr: bool) -> Vec<u8> {
+    let mut writer = Writer { data: Vec::new(), cdr };
+    if cdr {
+        writer.data.extend_from_slice(&CDR_LE_HEADER);
+    }
+    writer.write_u32(transforms.len() as u32);
+    for transform in transforms {
+        writer.write_transform(transform);
+    }
+    writer.data
+}
+
+pub fn decode_ros1(data: &[u8]) -> Result<Vec<Transform>, TfError> {
+    decode(data, false)
+}
+
+pub fn decode_cdr(data: &[u8]) -> Result<Vec<Transform>, TfError> {
+    decode(data, true)
+}
+
+pub fn encode_ros1(transforms: &[Transform]) -> Vec<u8> {
+    encode(transforms, false)
+}
+
+pub fn encode_cdr(transforms: &[Transform]) -> Vec<u8> {
+    encode(transforms, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_transforms() -> Vec<Transform> {
+        vec![
+            Transform {
+                frame_id       : "map".to_string(),
+                child_frame_id : "odom".to_string(),
+                translation    : NTranslation3::new(1.0, 2.0, 3.0),
+                rotation       : NQuaternion::from_quaternion(Quaternion::new(0.5, 0.5, 0.5, 0.5)),
+                stamp          : Stamp::from_nanos(1_500_000_123)
+            },
+            Transform {
+                frame_id       : "odom".to_string(),
+                child_frame_id : "base".to_string(),
+                translation    : NTranslation3::new(-1.0, 0.0, 0.5),
+                rotation       : NQuaternion::identity(),
+                stamp          : Stamp::from_nanos(1_600_000_000)
+            }
+        ]
+    }
+
+    fn check_round_trip(decoded: &[Transform], expected: &[Transform]) {
+        assert_eq!(expected.len(), decoded.len());
+        for (a, b) in decoded.iter().zip(expected.iter()) {
+            assert_eq!(b.frame_id, a.frame_id);
+            assert_eq!(b.child_frame_id, a.child_frame_id);
+            assert_eq!(b.stamp, a.stamp);
+            assert!(abs_diff_eq!(b.translation.vector.x, a.translation.vector.x));
+            assert!(abs_diff_eq!(b.rotation.coords.w, a.rotation.coords.w));
+        }
+    }
+
+    #[test]
+    fn test_ros1_round_trip() {
+        let transforms = make_transforms();
+        check_round_trip(&decode_ros1(&encode_ros1(&transforms)).unwrap(), &transforms);
+    }
+
+    #[test]
+    fn test_cdr_round_trip() {
+        let transforms = make_transforms();
+        let data = encode_cdr(&transforms);
+        // the payload ends with an aligned float64
+        assert_eq!(0, (data.len() - CDR_LE_HEADER.len()) % 8);
+        check_round_trip(&decode_cdr(&data).unwrap(), &transforms);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let data = encode_ros1(&make_transforms());
+        assert!(decode_ros1(&data[..data.len() - 3]).is_err());
+    }
+
+}
@@ -0,0 +1,297 @@
+use super::transform_storage::{FrameId, TransformStorage, Stamp};
+use super::interpolation::interpolate_two_transform;
+use super::time_cache_interface::*;
+
+use std::collections::VecDeque;
+
+use FindClosestResult::*;
+use TfError::*;
+
+// Samples up to `horizon` old (relative to the newest one) are kept at
+// most one per `period`. A zero period keeps every sample.
+#[derive(Debug, Clone)]
+pub struct Tier {
+    pub period  : Stamp,
+    pub horizon : Stamp
+}
+
+impl Tier {
+
+    pub fn new(period: Stamp, horizon: Stamp) -> Tier {
+        Tier {
+            period,
+            horizon
+        }
+    }
+
+}
+
+// Long history at decreasing resolution: samples that age out of a tier
+// move to the next one if they are at least its period apart, and are
+// dropped after the horizon of the last tier.
+#[derive(Clone)]
+pub struct TieredCache {
+    tiers   : Vec<Tier>,
+    // newest first within each tier, and every tier older than the previous one
+    samples : Vec<VecDeque<TransformStorage>>
+}
+
+impl TieredCache {
+
+    pub fn new(tiers: Vec<Tier>) -> Result<TieredCache, TfError> {
+        if tiers.is_empty() {
+            return Err(InvalidArgument("a tiered cache needs at least one tier".to_string()));
+        }
+        for pair in tiers.windows(2) {
+            if pair[1].horizon <= pair[0].horizon || pair[1].period < pair[0].period {
+                return Err(InvalidArgument("tiers must have increasing horizons and periods".to_string()));
+            }
+        }
+        let samples = tiers.iter().map(|_| VecDeque::new()).collect();
+        Ok(TieredCache {
+            tiers,
+            samples
+        })
+    }
+
+    pub fn tiers(&self) -> &[Tier] {
+        &self.tiers
+    }
+
+    pub fn tier_length(&self, tier: usize) -> usize {
+        self.samples[tier].len()
+    }
+
+    fn len(&self) -> usize {
+        self.samples.iter().map(|x| x.len()).sum()
+    }
+
+    // 0 is the newest sample
+    fn at(&self, mut index: usize) -> &TransformStorage {
+        for tier in self.samples.iter() {
+            if index < tier.len() {
+                return &tier[index];
+            }
+            index -= tier.len();
+        }
+        panic!("sample index out of range");
+    }
+
+    fn newest(&self) -> Option<&TransformStorage> {
+        self.samples.iter().find_map(|x| x.front())
+    }
+
+    fn oldest(&self) -> Option<&TransformStorage> {
+        self.samples.iter().rev().find_map(|x| x.back())
+    }
+
+    fn cascade(&mut self) {
+        let newest = match self.newest() {
+            Some(ts) => ts.stamp.nanos(),
+            None => return
+        };
+        for i in 0..self.tiers.len() {
+            let horizon = self.tiers[i].horizon.nanos();
+            while self.samples[i].back().map_or(false, |ts| newest - ts.stamp.nanos() > horizon) {
+                let ts = self.samples[i].pop_back().unwrap();
+                if i + 1 == self.tiers.len() {
+                    continue;
+                }
+                let keep = match self.samples[i + 1].front() {
+                    Some(previous) => ts.stamp.nanos() - previous.stamp.nanos() >= self.tiers[i + 1].period.nanos(),
+                    None => true
+                };
+                if keep {
+                    self.samples[i + 1].push_front(ts);
+                }
+            }
+        }
+    }
+
+    pub fn find_closest(&self, req_time: &Stamp) -> Result<FindClosestResult, TfError> {
+        let len = self.len();
+        if len == 0 {
+            return Ok(NoClose);
+        }
+        let latest_tran = self.newest().unwrap();
+        if *req_time == Stamp::from_nanos(0) || *req_time == latest_tran.stamp {
+            return Ok(OneClose(latest_tran));
+        }
+        if len == 1 {
+            return Err(ExtrapolationError1);
+        }
+        let earliest_tran = self.oldest().unwrap();
+        if *req_time == earliest_tran.stamp {
+            Ok(OneClose(earliest_tran))
+        } else if *req_time < earliest_tran.stamp {
+            Err(ExtrapolationError2)
+        } else if *req_time > latest_tran.stamp {
+            Err(ExtrapolationError3)
+        } else {
+            // first sample not newer than the requested time
+            let (mut low, mut high) = (0, len - 1);
+            while low < high {
+                let middle = (low + high) / 2;
+                if self.at(middle).stamp <= *req_time {
+                    high = middle;
+                } else {
+                    low = middle + 1;
+                }
+            }
+            let older_tran = self.at(low);
+            if older_tran.stamp == *req_time {
+                Ok(OneClose(older_tran))
+            } else {
+                Ok(TwoClose(self.at(low - 1), older_tran))
+            }
+        }
+    }
+
+}
+
+impl TimeCacheInterface for TieredCache {
+
+    fn get_data(&self, stamp: &Stamp) -> Result<TransformStorage, TfError> {
+        match self.find_closest(stamp)? {
+            NoClose => Err(TransformNotFound),
+            OneClose(ts) => Ok(ts.clone()),
+            TwoClose(newer_ts, older_ts) => {
+                if newer_ts.frame_id == older_ts.frame_id {
+                    Ok(interpolate_two_transform(newer_ts, older_ts, stamp))
+                } else {
+                    let mut ts = older_ts.clone();
+                    ts.stamp = stamp.clone();
+                    Ok(ts)
+                }
+            }
+        }
+    }
+
+    fn get_parent(&self, stamp: &Stamp) -> Result<FrameId, TfError> {
+        match self.find_closest(stamp)? {
+            NoClose => Err(NoParent),
+            OneClose(ts) => Ok(ts.frame_id),
+            TwoClose(_, older_ts) => Ok(older_ts.frame_id)
+        }
+    }
+
+    // samples older than the full-rate tier are rejected, so that the
+    // tiers stay ordered
+    fn insert_data(&mut self, new_ts: TransformStorage) -> bool {
+        if let Some(newest) = self.newest() {
+            if newest.stamp.nanos() - new_ts.stamp.nanos() > self.tiers[0].horizon.nanos() {
+                return false;
+            }
+        }
+        let full_rate = &mut self.samples[0];
+        let insert_point = full_rate.iter().filter(|x| x.stamp > new_ts.stamp).count();
+        full_rate.insert(insert_point, new_ts);
+        self.cascade();
+        true
+    }
+
+    fn clear(&mut self) {
+        for tier in self.samples.iter_mut() {
+            tier.clear();
+        }
+    }
+
+    fn get_latest_time_and_parent(&self) -> Option<(Stamp, FrameId)> {
+        let ts = self.newest()?;
+        Some((ts.stamp.clone(), ts.frame_id))
+    }
+
+    fn get_length(&self) -> usize {
+        self.len()
+    }
+
+    fn get_latest_timestamp(&self) -> Option<Stamp> {
+        Some(self.newest()?.stamp.clone())
+    }
+
+    fn get_oldest_timestamp(&self) -> Option<Stamp> {
+        Some(self.oldest()?.stamp.clone())
+    }
+
+    fn get_all_data(&self) -> Vec<TransformStorage> {
+        // oldest first
+        self.samples.iter().rev().flat_map(|tier| tier.iter().rev()).cloned().collect()
+    }
+
+    fn prune_older_than(&mut self, stamp: &Stamp) {
+        let mut len = self.len();
+        for tier in self.samples.iter_mut().rev() {
+            while len > 1 && tier.back().map_or(false, |ts| ts.stamp < *stamp) {
+                tier.pop_back();
+                len -= 1;
+            }
+        }
+    }
+
+    fn clone_cache(&self) -> Box<dyn TimeCacheInterface> {
+        Box::new(self.clone())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{NTranslation3, NQuaternion};
+
+    fn make_transform_storage(x: f64, nanos: i64) -> TransformStorage {
+        TransformStorage {
+            frame_id       : 1u32,
+            child_frame_id : 2u32,
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    fn make_cache() -> TieredCache {
+        TieredCache::new(vec![
+            Tier::new(Stamp::from_nanos(0), Stamp::from_nanos(100)),
+            Tier::new(Stamp::from_nanos(10), Stamp::from_nanos(1000)),
+            Tier::new(Stamp::from_nanos(100), Stamp::from_nanos(10000))
+        ]).unwrap()
+    }
+
+    #[test]
+    fn test_downsamples_with_age() {
+        let mut cache = make_cache();
+        for i in 0..20001 {
+            assert!(cache.insert_data(make_transform_storage(i as f64, i)));
+        }
+        assert_eq!(101, cache.tier_length(0));
+        assert!((89..=91).contains(&cache.tier_length(1)));
+        assert!((89..=91).contains(&cache.tier_length(2)));
+        assert!(cache.get_oldest_timestamp().unwrap() >= Stamp::from_nanos(10000));
+
+        // x follows the stamp, so interpolation is exact in every tier
+        for &t in [19995, 19450, 15555, 10050].iter() {
+            let ts = cache.get_data(&Stamp::from_nanos(t)).unwrap();
+            assert!(abs_diff_eq!(t as f64, ts.translation.vector.x, epsilon = 1.0e-9));
+        }
+        assert!(cache.get_data(&Stamp::from_nanos(5000)).is_err());
+
+        let all = cache.get_all_data();
+        assert_eq!(cache.get_length(), all.len());
+        assert!(all.windows(2).all(|x| x[0].stamp < x[1].stamp));
+    }
+
+    #[test]
+    fn test_rejects_old_samples_and_bad_tiers() {
+        let mut cache = make_cache();
+        cache.insert_data(make_transform_storage(0.0, 1000));
+        assert!(cache.insert_data(make_transform_storage(0.0, 950)));
+        assert!(!cache.insert_data(make_transform_storage(0.0, 800)));
+
+        assert!(TieredCache::new(Vec::new()).is_err());
+        assert!(TieredCache::new(vec![
+            Tier::new(Stamp::from_nanos(0), Stamp::from_nanos(100)),
+            Tier::new(Stamp::from_nanos(10), Stamp::from_nanos(50))
+        ]).is_err());
+    }
+
+}
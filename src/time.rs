@@ -0,0 +1,114 @@
+// Stand-ins for rosrust::{Time, Duration} when the crate is built without
+// the "ros" feature. Same fields and constructors, so the core code does
+// not need to know which one it gets.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+#[derive(Clone, Default)]
+pub struct Time {
+    pub sec  : u32,
+    pub nsec : u32
+}
+
+impl Time {
+
+    pub fn new() -> Time {
+        Time::default()
+    }
+
+    pub fn from_nanos(t: i64) -> Time {
+        Time {
+            sec  : (t / NANOS_PER_SEC) as u32,
+            nsec : (t % NANOS_PER_SEC) as u32
+        }
+    }
+
+    pub fn nanos(&self) -> i64 {
+        i64::from(self.sec) * NANOS_PER_SEC + i64::from(self.nsec)
+    }
+
+}
+
+#[derive(Clone, Default)]
+pub struct Duration {
+    pub sec  : i32,
+    pub nsec : i32
+}
+
+impl Duration {
+
+    pub fn new() -> Duration {
+        Duration::default()
+    }
+
+    pub fn from_nanos(t: i64) -> Duration {
+        Duration {
+            sec  : (t / NANOS_PER_SEC) as i32,
+            nsec : (t % NANOS_PER_SEC) as i32
+        }
+    }
+
+    pub fn from_seconds(sec: i32) -> Duration {
+        Duration {
+            sec,
+            nsec : 0
+        }
+    }
+
+    pub fn nanos(&self) -> i64 {
+        i64::from(self.sec) * NANOS_PER_SEC + i64::from(self.nsec)
+    }
+
+}
+
+// compared by value, so that 1s + 0ns equals 0s + 1e9ns
+macro_rules! impl_nanos_ord {
+    ($t:ty) => {
+        impl PartialEq for $t {
+            fn eq(&self, other: &$t) -> bool {
+                self.nanos() == other.nanos()
+            }
+        }
+
+        impl Eq for $t {}
+
+        impl PartialOrd for $t {
+            fn partial_cmp(&self, other: &$t) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $t {
+            fn cmp(&self, other: &$t) -> Ordering {
+                self.nanos().cmp(&other.nanos())
+            }
+        }
+
+        impl fmt::Debug for $t {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}ns", self.nanos())
+            }
+        }
+    };
+}
+
+impl_nanos_ord!(Time);
+impl_nanos_ord!(Duration);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nanos_round_trip() {
+        assert_eq!(1_500_000_000, Duration::from_nanos(1_500_000_000).nanos());
+        assert_eq!(-1_500_000_000, Duration::from_nanos(-1_500_000_000).nanos());
+        assert_eq!(Time::from_nanos(2_000_000_001).nanos(), 2_000_000_001);
+        assert!(Duration::from_nanos(-1) < Duration::from_seconds(0));
+        assert_eq!(Duration { sec: 1, nsec: 0 }, Duration { sec: 0, nsec: 1_000_000_000 });
+    }
+
+}
@@ -21,8 +21,16 @@ impl TimeCacheInterface for TimeCache {
             OneClose(ts) => {
                 Ok(ts.clone())
             },
-            TwoClose(left_ts, right_ts) => {
-                Ok(interpolate_two_transform(left_ts, right_ts, stamp))
+            TwoClose(newer_ts, older_ts) => {
+                if newer_ts.frame_id == older_ts.frame_id {
+                    Ok(interpolate_two_transform(newer_ts, older_ts, stamp))
+                } else {
+                    // the frame was re-parented in between, the older
+                    // sample holds until the newer one takes over
+                    let mut ts = older_ts.clone();
+                    ts.stamp = stamp.clone();
+                    Ok(ts)
+                }
             }
         }
     }
@@ -31,11 +39,14 @@ impl TimeCacheInterface for TimeCache {
     {
         let closest_res = self.find_closest(stamp)?;
         match(closest_res) {
-            TwoClose(left_ts, right_ts) => {
-                Ok(left_ts.frame_id)
-            }
-            _ => {
+            NoClose => {
                 Err(NoParent)
+            },
+            OneClose(ts) => {
+                Ok(ts.frame_id)
+            },
+            TwoClose(_, older_ts) => {
+                Ok(older_ts.frame_id)
             }
         }
     }
@@ -66,11 +77,26 @@ impl TimeCacheInterface for TimeCache {
         Some(self.transforms_ordered.back()?.stamp.clone())
     }
 
+    fn get_all_data(&self) -> Vec<TransformStorage> {
+        // oldest first
+        self.transforms_ordered.iter().rev().cloned().collect()
+    }
+
+    fn prune_older_than(&mut self, stamp: &Stamp) {
+        while self.transforms_ordered.len() > 1 && self.transforms_ordered.back().unwrap().stamp < *stamp {
+            self.transforms_ordered.pop_back();
+        }
+    }
+
+    fn clone_cache(&self) -> Box<dyn TimeCacheInterface> {
+        Box::new(self.clone())
+    }
+
 }
 
 impl TimeCache {
 
-    fn new() -> TimeCache {
+    pub fn new() -> TimeCache {
         TimeCache {
             transforms_ordered: VecDeque::new() 
         }
@@ -115,17 +141,91 @@ impl TimeCache {
                     Err(ExtrapolationError2)
                 } else if *req_time > *latest_stamp {
                     Err(ExtrapolationError3)
+                } else if let Some(exact_tran) = self.transforms_ordered.iter().find(|x| x.stamp == *req_time) {
+                    Ok(OneClose(exact_tran))
                 } else {
-                   let left_index = self.transforms_ordered.iter().filter(|x| *req_time > x.stamp).count() - 1;
-                   let left_tran = self.transforms_ordered.get(left_index).unwrap();
-                   let right_tran = self.transforms_ordered.get(left_index + 1).unwrap();
-                   Ok(TwoClose(left_tran, right_tran))
+                   // newer sample first, like the storage
+                   let newer_index = self.transforms_ordered.iter().filter(|x| x.stamp > *req_time).count() - 1;
+                   let newer_tran = self.transforms_ordered.get(newer_index).unwrap();
+                   let older_tran = self.transforms_ordered.get(newer_index + 1).unwrap();
+                   Ok(TwoClose(newer_tran, older_tran))
                 }
             }
         } else {
             Ok(NoClose)
         }
     }
+
+    // stored samples with start <= stamp <= end, oldest first
+    pub fn range<'a>(&'a self, start: &Stamp, end: &Stamp) -> impl Iterator<Item = &'a TransformStorage> + 'a {
+        let (start, end) = (start.clone(), end.clone());
+        self.transforms_ordered.iter().rev()
+            .skip_while(move |x| x.stamp < start)
+            .take_while(move |x| x.stamp <= end)
+    }
+
+    // Interpolated samples every `period` from `start`, limited to the
+    // stored time span
+    pub fn resample(&self, start: &Stamp, end: &Stamp, period: &Stamp) -> Result<Resample, TfError> {
+        if period.nanos() <= 0 {
+            return Err(InvalidArgument("the resampling period must be positive".to_string()));
+        }
+        let period = period.nanos();
+        let mut next = start.nanos();
+        let mut last = end.nanos();
+        if let (Some(oldest), Some(newest)) = (self.transforms_ordered.back(), self.transforms_ordered.front()) {
+            if next < oldest.stamp.nanos() {
+                next += (oldest.stamp.nanos() - next + period - 1) / period * period;
+            }
+            last = last.min(newest.stamp.nanos());
+        }
+        Ok(Resample {
+            samples : &self.transforms_ordered,
+            older   : 0,
+            next,
+            last,
+            period
+        })
+    }
+}
+
+pub struct Resample<'a> {
+    samples : &'a VecDeque<TransformStorage>,
+    // the latest sample not after the next step, counting from the oldest
+    older   : usize,
+    next    : i64,
+    last    : i64,
+    period  : i64
+}
+
+impl<'a> Iterator for Resample<'a> {
+    type Item = TransformStorage;
+
+    fn next(&mut self) -> Option<TransformStorage> {
+        let samples = self.samples;
+        let len = samples.len();
+        if len == 0 || self.next > self.last {
+            return None;
+        }
+        let oldest_first = |index: usize| &samples[len - 1 - index];
+        let stamp = Stamp::from_nanos(self.next);
+        self.next += self.period;
+        while self.older + 1 < len && oldest_first(self.older + 1).stamp <= stamp {
+            self.older += 1;
+        }
+        let older_ts = oldest_first(self.older);
+        if older_ts.stamp == stamp {
+            return Some(older_ts.clone());
+        }
+        let newer_ts = oldest_first(self.older + 1);
+        if newer_ts.frame_id == older_ts.frame_id {
+            Some(interpolate_two_transform(newer_ts, older_ts, &stamp))
+        } else {
+            let mut ts = older_ts.clone();
+            ts.stamp = stamp;
+            Some(ts)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -368,4 +468,118 @@ mod tests {
         
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_prune_older_than() {
+        let mut time_cache = TimeCache::new();
+        time_cache.insert_ordered_by_time(make_transform_storage_with_stamp(Stamp::from_nanos(100)));    
+        time_cache.insert_ordered_by_time(make_transform_storage_with_stamp(Stamp::from_nanos(200)));    
+        time_cache.insert_ordered_by_time(make_transform_storage_with_stamp(Stamp::from_nanos(300)));    
+
+        time_cache.prune_older_than(&Stamp::from_nanos(200));
+        assert_eq!(2usize, time_cache.len());
+        assert_eq!(Some(Stamp::from_nanos(200)), time_cache.get_oldest_timestamp());
+
+        time_cache.prune_older_than(&Stamp::from_nanos(1000));
+        assert_eq!(1usize, time_cache.len());
+        assert_eq!(Some(Stamp::from_nanos(300)), time_cache.get_latest_timestamp());
+    }
+
+    fn make_transform_storage_with_parent(parent: FrameId, x: f64, stamp: i64) -> TransformStorage {
+        TransformStorage {
+            frame_id       : parent,
+            child_frame_id : 3u32,
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(stamp)
+        }
+    }
+
+    #[test]
+    fn test_find_closest_not_in_the_middle() {
+        let mut time_cache = TimeCache::new();
+        for i in 1..5 {
+            time_cache.insert_ordered_by_time(make_transform_storage_with_stamp(Stamp::from_nanos(i * 100)));
+        }
+        match time_cache.find_closest(&Stamp::from_nanos(350)).unwrap() {
+            TwoClose(newer_tran, older_tran) => {
+                assert_eq!(Stamp::from_nanos(400), newer_tran.stamp);
+                assert_eq!(Stamp::from_nanos(300), older_tran.stamp);
+            },
+            res => assert!(false, "result {:?} was not expected", res)
+        }
+        match time_cache.find_closest(&Stamp::from_nanos(200)).unwrap() {
+            OneClose(res_transform) => assert_eq!(Stamp::from_nanos(200), res_transform.stamp),
+            res => assert!(false, "result {:?} was not expected", res)
+        }
+    }
+
+    #[test]
+    fn test_get_parent_single_sample() {
+        let mut time_cache = TimeCache::new();
+        time_cache.insert_ordered_by_time(make_transform_storage_with_parent(1, 0.0, 100));
+        assert_eq!(1u32, time_cache.get_parent(&Stamp::from_nanos(100)).unwrap());
+        assert_eq!(1u32, time_cache.get_parent(&Stamp::from_nanos(0)).unwrap());
+        assert!(time_cache.get_parent(&Stamp::from_nanos(150)).is_err());
+    }
+
+    #[test]
+    fn test_reparenting() {
+        let mut time_cache = TimeCache::new();
+        time_cache.insert_ordered_by_time(make_transform_storage_with_parent(1, 1.0, 100));
+        time_cache.insert_ordered_by_time(make_transform_storage_with_parent(1, 2.0, 200));
+        time_cache.insert_ordered_by_time(make_transform_storage_with_parent(5, 0.0, 300));
+        time_cache.insert_ordered_by_time(make_transform_storage_with_parent(5, 0.0, 400));
+
+        assert_eq!(1u32, time_cache.get_parent(&Stamp::from_nanos(150)).unwrap());
+        assert_eq!(1u32, time_cache.get_parent(&Stamp::from_nanos(200)).unwrap());
+        assert_eq!(1u32, time_cache.get_parent(&Stamp::from_nanos(250)).unwrap());
+        assert_eq!(5u32, time_cache.get_parent(&Stamp::from_nanos(300)).unwrap());
+        assert_eq!(5u32, time_cache.get_parent(&Stamp::from_nanos(0)).unwrap());
+
+        // no interpolation between the two parents
+        let ts = time_cache.get_data(&Stamp::from_nanos(250)).unwrap();
+        assert_eq!(1u32, ts.frame_id);
+        assert!(abs_diff_eq!(2.0, ts.translation.vector.x));
+        assert_eq!(Stamp::from_nanos(250), ts.stamp);
+    }
+
+    #[test]
+    fn test_range() {
+        let mut time_cache = TimeCache::new();
+        for i in 1..6 {
+            time_cache.insert_ordered_by_time(make_transform_storage_with_parent(1, i as f64, i * 100));
+        }
+        let stamps: Vec<i64> = time_cache.range(&Stamp::from_nanos(150), &Stamp::from_nanos(400))
+            .map(|x| x.stamp.nanos()).collect();
+        assert_eq!(vec![200, 300, 400], stamps);
+        assert_eq!(5, time_cache.range(&Stamp::from_nanos(0), &Stamp::from_nanos(1000)).count());
+        assert_eq!(0, time_cache.range(&Stamp::from_nanos(600), &Stamp::from_nanos(1000)).count());
+    }
+
+    #[test]
+    fn test_resample() {
+        let mut time_cache = TimeCache::new();
+        for i in 1..4 {
+            time_cache.insert_ordered_by_time(make_transform_storage_with_parent(1, i as f64, i * 100));
+        }
+        time_cache.insert_ordered_by_time(make_transform_storage_with_parent(5, 0.0, 400));
+
+        // the steps before the first sample are skipped, the others keep the start as origin
+        let samples: Vec<TransformStorage> = time_cache.resample(&Stamp::from_nanos(25), &Stamp::from_nanos(1000),
+                                                                 &Stamp::from_nanos(50)).unwrap().collect();
+        let stamps: Vec<i64> = samples.iter().map(|x| x.stamp.nanos()).collect();
+        assert_eq!(vec![125, 175, 225, 275, 325, 375], stamps);
+        assert!(abs_diff_eq!(1.25, samples[0].translation.vector.x));
+        assert!(abs_diff_eq!(2.75, samples[3].translation.vector.x));
+        // not interpolated towards the new parent
+        assert_eq!(1u32, samples[5].frame_id);
+        assert!(abs_diff_eq!(3.0, samples[5].translation.vector.x));
+
+        assert_eq!(4, time_cache.resample(&Stamp::from_nanos(100), &Stamp::from_nanos(400),
+                                          &Stamp::from_nanos(100)).unwrap().count());
+        assert!(time_cache.resample(&Stamp::from_nanos(100), &Stamp::from_nanos(400), &Stamp::from_nanos(0)).is_err());
+        assert_eq!(0, TimeCache::new().resample(&Stamp::from_nanos(100), &Stamp::from_nanos(400),
+                                                &Stamp::from_nanos(10)).unwrap().count());
+    }
+
+}
@@ -4,8 +4,10 @@ use super::interpolation::interpolate_two_transform;
 use approx;
 
 use std::collections::VecDeque;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 
-pub trait TimeCacheInterface {
+pub trait TimeCacheInterface: Send + Sync {
     fn get_data(&self, stamp: &Stamp) -> Result<TransformStorage, TfError>;
     fn get_parent(&self, stamp: &Stamp) -> Result<FrameId, TfError>;
     fn insert_data(&mut self, new_ts: TransformStorage) -> bool;
@@ -14,9 +16,17 @@ pub trait TimeCacheInterface {
     fn get_length(&self) -> usize;
     fn get_latest_timestamp(&self) -> Option<Stamp>;
     fn get_oldest_timestamp(&self) -> Option<Stamp>;
+    fn get_all_data(&self) -> Vec<TransformStorage>;
+    // drops samples older than `stamp`, always keeping the newest one
+    fn prune_older_than(&mut self, _stamp: &Stamp) {}
+    // a deep copy, used when a buffer view still shares this cache
+    fn clone_cache(&self) -> Box<dyn TimeCacheInterface>;
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TimeCache {
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialization::newest_first"))]
     pub transforms_ordered: VecDeque<TransformStorage>
 }
 
@@ -27,7 +37,46 @@ pub enum TfError {
     ExtrapolationError1,
     ExtrapolationError2,
     ExtrapolationError3,
-    NoParent
+    // an extrapolation reported by a remote buffer, with its description
+    ExtrapolationError(String),
+    NoParent,
+    // a sample the cache of its frame did not accept, like tf2's TF_OLD_DATA
+    OldData(String),
+    LookupError(String),
+    ConnectivityError(String),
+    Io(std::io::Error),
+    Decode(String),
+    Ros(String),
+    InvalidArgument(String)
+}
+
+impl std::fmt::Display for TfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TfError::Generic(description) => write!(f, "{}", description),
+            TfError::TransformNotFound => write!(f, "transform not found"),
+            TfError::ExtrapolationError1 => write!(f, "lookup would require extrapolation, only one transform is available"),
+            TfError::ExtrapolationError2 => write!(f, "lookup would require extrapolation into the past"),
+            TfError::ExtrapolationError3 => write!(f, "lookup would require extrapolation into the future"),
+            TfError::ExtrapolationError(description) => write!(f, "{}", description),
+            TfError::NoParent => write!(f, "frame has no parent"),
+            TfError::OldData(description) => write!(f, "{}", description),
+            TfError::LookupError(description) => write!(f, "{}", description),
+            TfError::ConnectivityError(description) => write!(f, "{}", description),
+            TfError::Io(err) => write!(f, "{}", err),
+            TfError::Decode(description) => write!(f, "{}", description),
+            TfError::Ros(description) => write!(f, "{}", description),
+            TfError::InvalidArgument(description) => write!(f, "{}", description)
+        }
+    }
+}
+
+impl std::error::Error for TfError {}
+
+impl From<std::io::Error> for TfError {
+    fn from(err: std::io::Error) -> TfError {
+        TfError::Io(err)
+    }
 }
 
 #[derive(Debug)]
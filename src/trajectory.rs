@@ -0,0 +1,564 @@
+use super::transform_storage::{Stamp, NTranslation3, NQuaternion};
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{Buffer, FrameStatistics, Transform};
+
+use nalgebra::{Matrix3, Quaternion, Rotation3};
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvColumn {
+    // seconds
+    Time,
+    TimeNanos,
+    X,
+    Y,
+    Z,
+    Qx,
+    Qy,
+    Qz,
+    Qw,
+    // radians
+    Roll,
+    Pitch,
+    Yaw
+}
+
+impl CsvColumn {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CsvColumn::Time => "time",
+            CsvColumn::TimeNanos => "time_ns",
+            CsvColumn::X => "x",
+            CsvColumn::Y => "y",
+            CsvColumn::Z => "z",
+            CsvColumn::Qx => "qx",
+            CsvColumn::Qy => "qy",
+            CsvColumn::Qz => "qz",
+            CsvColumn::Qw => "qw",
+            CsvColumn::Roll => "roll",
+            CsvColumn::Pitch => "pitch",
+            CsvColumn::Yaw => "yaw"
+        }
+    }
+
+    fn value(&self, transform: &Transform) -> String {
+        let t = &transform.translation.vector;
+        let q = &transform.rotation.coords;
+        let (roll, pitch, yaw) = transform.rotation.euler_angles();
+        match self {
+            CsvColumn::Time => format_seconds(transform.stamp.nanos()),
+            CsvColumn::TimeNanos => transform.stamp.nanos().to_string(),
+            CsvColumn::X => t.x.to_string(),
+            CsvColumn::Y => t.y.to_string(),
+            CsvColumn::Z => t.z.to_string(),
+            CsvColumn::Qx => q.x.to_string(),
+            CsvColumn::Qy => q.y.to_string(),
+            CsvColumn::Qz => q.z.to_string(),
+            CsvColumn::Qw => q.w.to_string(),
+            CsvColumn::Roll => roll.to_string(),
+            CsvColumn::Pitch => pitch.to_string(),
+            CsvColumn::Yaw => yaw.to_string()
+        }
+    }
+
+}
+
+impl FromStr for CsvColumn {
+    type Err = TfError;
+
+    fn from_str(name: &str) -> Result<CsvColumn, TfError> {
+        let columns = [CsvColumn::Time, CsvColumn::TimeNanos, CsvColumn::X, CsvColumn::Y, CsvColumn::Z,
+                       CsvColumn::Qx, CsvColumn::Qy, CsvColumn::Qz, CsvColumn::Qw,
+                       CsvColumn::Roll, CsvColumn::Pitch, CsvColumn::Yaw];
+        columns.iter().find(|x| x.name() == name.trim()).cloned()
+            .ok_or_else(|| TfError::InvalidArgument(format!("unknown csv column {}", name)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvLayout {
+    pub columns   : Vec<CsvColumn>,
+    pub delimiter : char,
+    pub header    : bool
+}
+
+impl Default for CsvLayout {
+    fn default() -> CsvLayout {
+        CsvLayout {
+            columns   : vec![CsvColumn::Time, CsvColumn::X, CsvColumn::Y, CsvColumn::Z,
+                             CsvColumn::Qx, CsvColumn::Qy, CsvColumn::Qz, CsvColumn::Qw],
+            delimiter : ',',
+            header    : true
+        }
+    }
+}
+
+impl CsvLayout {
+
+    // e.g. "time,x,y,yaw"
+    pub fn from_columns(spec: &str) -> Result<CsvLayout, TfError> {
+        let columns = spec.split(',').map(|x| x.parse()).collect::<Result<Vec<CsvColumn>, TfError>>()?;
+        Ok(CsvLayout {
+            columns,
+            ..CsvLayout::default()
+        })
+    }
+
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrajectoryFormat {
+    // "t tx ty tz qx qy qz qw"
+    Tum,
+    // the first three rows of the pose matrix, without stamps
+    Kitti,
+    Csv(CsvLayout)
+}
+
+impl FromStr for TrajectoryFormat {
+    type Err = TfError;
+
+    fn from_str(name: &str) -> Result<TrajectoryFormat, TfError> {
+        match name {
+            "tum" => Ok(TrajectoryFormat::Tum),
+            "kitti" => Ok(TrajectoryFormat::Kitti),
+            "csv" => Ok(TrajectoryFormat::Csv(CsvLayout::default())),
+            _ => Err(TfError::InvalidArgument(format!("unknown trajectory format {}", name)))
+        }
+    }
+}
+
+fn format_seconds(nanos: i64) -> String {
+    let sign = if nanos < 0 { "-" } else { "" };
+    let nanos = nanos.abs();
+    format!("{}{}.{:09}", sign, nanos / 1_000_000_000, nanos % 1_000_000_000)
+}
+
+// The pose of `frame` in `reference_frame` between `start` and `end`,
+// every `period` or at the stamps of the samples of `frame` if None.
+// Times the buffer cannot answer without extrapolating are left out.
+pub fn sample_trajectory(buffer: &Buffer, reference_frame: &str, frame: &str, start: &Stamp, end: &Stamp,
+                         period: Option<&Stamp>) -> Result<Vec<Transform>, TfError> {
+    let mut ids = Vec::new();
+    for name in [reference_frame, frame].iter() {
+        ids.push(buffer.get_frame_number(name)
+            .ok_or_else(|| TfError::LookupError(format!("\"{}\" does not exist", name)))?);
+    }
+    let stamps: Vec<i64> = match period {
+        Some(period) if period.nanos() > 0 => {
+            // the steps stay on the grid of `start`, within the recorded span
+            let dynamic: Vec<FrameStatistics> = buffer.get_frame_statistics().into_iter().filter(|x| !x.is_static).collect();
+            let first = dynamic.iter().map(|x| x.oldest.nanos()).min();
+            let last = dynamic.iter().map(|x| x.most_recent.nanos()).max();
+            match (first, last) {
+                (Some(first), Some(last)) => {
+                    let period = period.nanos();
+                    let mut stamp = start.nanos();
+                    if stamp < first {
+                        let steps = (i128::from(first) - i128::from(stamp) + i128::from(period) - 1) / i128::from(period);
+                        stamp = (i128::from(stamp) + steps * i128::from(period)) as i64;
+                    }
+                    let mut stamps = Vec::new();
+                    while stamp <= end.nanos().min(last) {
+                        stamps.push(stamp);
+                        stamp = match stamp.checked_add(period) {
+                            Some(next) => next,
+                            None => break
+                        };
+                    }
+                    stamps
+                },
+                // a static chain is the same at any time
+                _ => vec![start.nanos()]
+            }
+        },
+        Some(_) => return Err(TfError::InvalidArgument("the sampling period must be positive".to_string())),
+        None => {
+            let id = ids[1];
+            if buffer.is_static(id) {
+                return Err(TfError::InvalidArgument(format!("{} is static, a sampling period is needed", frame)));
+            }
+            buffer.get_cache(id).map(|x| x.get_all_data()).unwrap_or_default().iter()
+                .map(|x| x.stamp.nanos())
+                .filter(|x| *x >= start.nanos() && *x <= end.nanos())
+                .collect()
+        }
+    };
+
+    let mut trajectory = Vec::new();
+    for stamp in stamps {
+        match buffer.lookup_transform(reference_frame, frame, &Stamp::from_nanos(stamp)) {
+            Ok(transform) => trajectory.push(transform),
+            Err(TfError::ExtrapolationError1) | Err(TfError::ExtrapolationError2) | Err(TfError::ExtrapolationError3) |
+            Err(TfError::ExtrapolationError(_)) => {},
+            Err(err) => return Err(err)
+        }
+    }
+    Ok(trajectory)
+}
+
+pub fn write_trajectory<W: Write>(mut writer: W, trajectory: &[Transform], format: &TrajectoryFormat) -> Result<(), TfError> {
+    if let TrajectoryFormat::Csv(layout) = format {
+        if layout.header {
+            let names: Vec<&str> = layout.columns.iter().map(|x| x.name()).collect();
+            writeln!(writer, "{}", names.join(&layout.delimiter.to_string()))?;
+        }
+    }
+    for transform in trajectory.iter() {
+        let t = &transform.translation.vector;
+        let q = &transform.rotation.coords;
+        match format {
+            TrajectoryFormat::Tum => {
+                writeln!(writer, "{} {} {} {} {} {} {} {}",
+                         format_seconds(transform.stamp.nanos()), t.x, t.y, t.z, q.x, q.y, q.z, q.w)?;
+            },
+            TrajectoryFormat::Kitti => {
+                let r = transform.rotation.to_rotation_matrix();
+                let r = r.matrix();
+                writeln!(writer, "{} {} {} {} {} {} {} {} {} {} {} {}",
+                         r[(0, 0)], r[(0, 1)], r[(0, 2)], t.x,
+                         r[(1, 0)], r[(1, 1)], r[(1, 2)], t.y,
+                         r[(2, 0)], r[(2, 1)], r[(2, 2)], t.z)?;
+            },
+            TrajectoryFormat::Csv(layout) => {
+                let values: Vec<String> = layout.columns.iter().map(|x| x.value(transform)).collect();
+                writeln!(writer, "{}", values.join(&layout.delimiter.to_string()))?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn export_trajectory<P: AsRef<Path>>(path: P, trajectory: &[Transform], format: &TrajectoryFormat) -> Result<(), TfError> {
+    write_trajectory(BufWriter::new(File::create(path)?), trajectory, format)
+}
+
+// The stamp of each pose in seconds, one per line, what read_times reads.
+// KITTI poses have no stamps, this is the times.txt that goes with them.
+pub fn write_times<W: Write>(mut writer: W, trajectory: &[Transform]) -> Result<(), TfError> {
+    for transform in trajectory.iter() {
+        writeln!(writer, "{}", format_seconds(transform.stamp.nanos()))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn export_times<P: AsRef<Path>>(path: P, trajectory: &[Transform]) -> Result<(), TfError> {
+    write_times(BufWriter::new(File::create(path)?), trajectory)
+}
+
+fn decode_error(line: usize, description: &str) -> TfError {
+    TfError::Decode(format!("trajectory line {}: {}", line, description))
+}
+
+// exact to the nanosecond, unlike going through f64
+fn parse_exact_seconds(value: &str) -> Option<i64> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value)
+    };
+    let mut parts = value.splitn(2, '.');
+    let sec: i64 = parts.next()?.parse().ok()?;
+    let nsec = match parts.next() {
+        Some(fraction) if fraction.len() <= 9 && fraction.chars().all(|x| x.is_ascii_digit()) =>
+            format!("{:0<9}", fraction).parse::<i64>().ok()?,
+        Some(_) => return None,
+        None => 0
+    };
+    let nanos = sec.checked_mul(1_000_000_000)?.checked_add(nsec)?;
+    Some(if negative { -nanos } else { nanos })
+}
+
+// exponents and fractions finer than a nanosecond go through f64
+fn parse_seconds(value: &str) -> Option<i64> {
+    parse_exact_seconds(value).or_else(|| {
+        let nanos = (value.parse::<f64>().ok()? * 1.0e9).round();
+        if nanos.is_finite() && nanos.abs() < i64::max_value() as f64 { Some(nanos as i64) } else { None }
+    })
+}
+
+// a zero or non-finite quaternion would normalize to NaN
+fn parse_rotation(line: usize, x: f64, y: f64, z: f64, w: f64) -> Result<NQuaternion, TfError> {
+    let q = Quaternion::new(w, x, y, z);
+    let norm = q.norm();
+    if !norm.is_finite() || norm == 0.0 {
+        return Err(decode_error(line, "invalid quaternion"));
+    }
+    Ok(NQuaternion::from_quaternion(q))
+}
+
+fn parse_values(line: usize, fields: &[&str]) -> Result<Vec<f64>, TfError> {
+    fields.iter().map(|x| x.trim().parse::<f64>().map_err(|_| decode_error(line, &format!("{} is not a number", x))))
+        .collect()
+}
+
+fn make_transform(parent: &str, child: &str, nanos: i64, translation: NTranslation3, rotation: NQuaternion) -> Transform {
+    Transform {
+        frame_id       : parent.to_string(),
+        child_frame_id : child.to_string(),
+        translation,
+        rotation,
+        stamp          : Stamp::from_nanos(nanos)
+    }
+}
+
+fn read_csv_line(line: usize, text: &str, layout: &CsvLayout) -> Result<(i64, NTranslation3, NQuaternion), TfError> {
+    let fields: Vec<&str> = text.split(layout.delimiter).collect();
+    if fields.len() != layout.columns.len() {
+        return Err(decode_error(line, &format!("expected {} columns", layout.columns.len())));
+    }
+    let mut nanos = None;
+    let mut t = [0.0; 3];
+    let mut q = [None; 4];
+    let mut rpy = [0.0; 3];
+    for (column, field) in layout.columns.iter().zip(fields.iter()) {
+        let field = field.trim();
+        match column {
+            CsvColumn::Time => nanos = Some(parse_seconds(field).ok_or_else(|| decode_error(line, "invalid time"))?),
+            CsvColumn::TimeNanos => nanos = Some(field.parse().map_err(|_| decode_error(line, "invalid time"))?),
+            _ => {
+                let value = parse_values(line, &[field])?[0];
+                match column {
+                    CsvColumn::X => t[0] = value,
+                    CsvColumn::Y => t[1] = value,
+                    CsvColumn::Z => t[2] = value,
+                    CsvColumn::Qx => q[0] = Some(value),
+                    CsvColumn::Qy => q[1] = Some(value),
+                    CsvColumn::Qz => q[2] = Some(value),
+                    CsvColumn::Qw => q[3] = Some(value),
+                    CsvColumn::Roll => rpy[0] = value,
+                    CsvColumn::Pitch => rpy[1] = value,
+                    _ => rpy[2] = value
+                }
+            }
+        }
+    }
+    let nanos = nanos.ok_or_else(|| decode_error(line, "the layout has no time column"))?;
+    let rotation = match q {
+        [Some(x), Some(y), Some(z), Some(w)] => parse_rotation(line, x, y, z, w)?,
+        [None, None, None, None] => NQuaternion::from_euler_angles(rpy[0], rpy[1], rpy[2]),
+        _ => return Err(decode_error(line, "the layout has only part of the quaternion"))
+    };
+    Ok((nanos, NTranslation3::new(t[0], t[1], t[2]), rotation))
+}
+
+// KITTI files have no stamps, they come from `times`, e.g. the times.txt
+// of the sequence. Blank lines and lines starting with '#' are skipped.
+pub fn read_trajectory<R: BufRead>(reader: R, format: &TrajectoryFormat, parent: &str, child: &str,
+                                   times: Option<&[Stamp]>) -> Result<Vec<Transform>, TfError> {
+    let mut trajectory = Vec::new();
+    let mut skip_header = match format {
+        TrajectoryFormat::Csv(layout) => layout.header,
+        _ => false
+    };
+    for (index, text) in reader.lines().enumerate() {
+        let text = text?;
+        let line = index + 1;
+        if text.trim().is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if skip_header {
+            skip_header = false;
+            continue;
+        }
+        let (nanos, translation, rotation) = match format {
+            TrajectoryFormat::Tum => {
+                let fields: Vec<&str> = text.split_whitespace().collect();
+                if fields.len() != 8 {
+                    return Err(decode_error(line, "expected t tx ty tz qx qy qz qw"));
+                }
+                let nanos = parse_seconds(fields[0]).ok_or_else(|| decode_error(line, "invalid time"))?;
+                let v = parse_values(line, &fields[1..])?;
+                (nanos, NTranslation3::new(v[0], v[1], v[2]), parse_rotation(line, v[3], v[4], v[5], v[6])?)
+            },
+            TrajectoryFormat::Kitti => {
+                let fields: Vec<&str> = text.split_whitespace().collect();
+                if fields.len() != 12 {
+                    return Err(decode_error(line, "expected the 12 values of a 3x4 matrix"));
+                }
+                let v = parse_values(line, &fields)?;
+                let times = times.ok_or_else(|| TfError::InvalidArgument("KITTI poses need their times".to_string()))?;
+                let stamp = times.get(trajectory.len()).ok_or_else(|| decode_error(line, "more poses than times"))?;
+                let r = Matrix3::new(v[0], v[1], v[2], v[4], v[5], v[6], v[8], v[9], v[10]);
+                (stamp.nanos(), NTranslation3::new(v[3], v[7], v[11]),
+                 NQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(r)))
+            },
+            TrajectoryFormat::Csv(layout) => read_csv_line(line, &text, layout)?
+        };
+        trajectory.push(make_transform(parent, child, nanos, translation, rotation));
+    }
+    Ok(trajectory)
+}
+
+// one time in seconds per line
+pub fn read_times<R: BufRead>(reader: R) -> Result<Vec<Stamp>, TfError> {
+    let mut times = Vec::new();
+    for (index, text) in reader.lines().enumerate() {
+        let text = text?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let nanos = parse_seconds(text.trim()).ok_or_else(|| decode_error(index + 1, "invalid time"))?;
+        times.push(Stamp::from_nanos(nanos));
+    }
+    Ok(times)
+}
+
+// Adds a trajectory file as the dynamic frame `parent -> child`, so it
+// can be looked up like any other frame. Returns the number of poses read.
+pub fn import_trajectory<P: AsRef<Path>>(path: P, format: &TrajectoryFormat, parent: &str, child: &str,
+                                         times: Option<&[Stamp]>, buffer: &mut Buffer) -> Result<usize, TfError> {
+    let trajectory = read_trajectory(BufReader::new(File::open(path)?), format, parent, child, times)?;
+    for transform in trajectory.iter() {
+        buffer.set_transform(transform, false)?;
+    }
+    Ok(trajectory.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{NTranslation3, NQuaternion, NVector3};
+
+    fn make_transform(parent: &str, child: &str, x: f64, yaw: f64, nanos: i64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::new(NVector3::z() * yaw),
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    fn make_buffer() -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.set_transform(&make_transform("map", "odom", 1.0, 0.0, 0), true).unwrap();
+        for i in 0..3 {
+            buffer.set_transform(&make_transform("odom", "base_link", i as f64, 0.0, 1_000_000_000 + i * 500_000_000), false).unwrap();
+        }
+        buffer
+    }
+
+    fn to_string(trajectory: &[Transform], format: &TrajectoryFormat) -> String {
+        let mut output = Vec::new();
+        write_trajectory(&mut output, trajectory, format).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_sample_trajectory() {
+        let buffer = make_buffer();
+        let all = sample_trajectory(&buffer, "map", "base_link", &Stamp::from_nanos(0),
+                                    &Stamp::from_nanos(i64::max_value()), None).unwrap();
+        assert_eq!(3, all.len());
+        assert!(abs_diff_eq!(3.0, all[2].translation.vector.x));
+
+        // the steps outside of the samples are skipped
+        let sampled = sample_trajectory(&buffer, "map", "base_link", &Stamp::from_nanos(750_000_000),
+                                        &Stamp::from_nanos(2_000_000_000), Some(&Stamp::from_nanos(250_000_000))).unwrap();
+        let xs: Vec<f64> = sampled.iter().map(|x| x.translation.vector.x).collect();
+        assert_eq!(vec![1.0, 1.5, 2.0, 2.5, 3.0], xs);
+
+        // open ended, as tf_trajectory asks without --end
+        let period = Stamp::from_nanos(500_000_000);
+        let sampled = sample_trajectory(&buffer, "map", "base_link", &Stamp::from_nanos(-1_000_000_000),
+                                        &Stamp::from_nanos(i64::max_value()), Some(&period)).unwrap();
+        assert_eq!(3, sampled.len());
+        assert!(sample_trajectory(&buffer, "map", "base_link", &Stamp::from_nanos(i64::min_value()),
+                                  &Stamp::from_nanos(i64::max_value()), Some(&period)).is_ok());
+
+        assert!(sample_trajectory(&buffer, "map", "odom", &Stamp::from_nanos(0), &Stamp::from_nanos(1), None).is_err());
+        assert!(sample_trajectory(&buffer, "map", "laser", &Stamp::from_nanos(0), &Stamp::from_nanos(1),
+                                  Some(&Stamp::from_nanos(1))).is_err());
+    }
+
+    #[test]
+    fn test_formats() {
+        let trajectory = vec![make_transform("map", "base_link", 1.5, std::f64::consts::FRAC_PI_2, 1_250_000_000)];
+
+        let tum = to_string(&trajectory, &TrajectoryFormat::Tum);
+        let values: Vec<f64> = tum.split_whitespace().map(|x| x.parse().unwrap()).collect();
+        assert!(tum.starts_with("1.250000000 1.5 0 0 "));
+        assert!(abs_diff_eq!(0.5f64.sqrt(), values[6], epsilon = 1.0e-9));
+        assert!(abs_diff_eq!(0.5f64.sqrt(), values[7], epsilon = 1.0e-9));
+
+        let kitti = to_string(&trajectory, &TrajectoryFormat::Kitti);
+        let expected = [0.0, -1.0, 0.0, 1.5, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let values: Vec<f64> = kitti.split_whitespace().map(|x| x.parse().unwrap()).collect();
+        assert_eq!(12, values.len());
+        for (value, expected) in values.iter().zip(expected.iter()) {
+            assert!(abs_diff_eq!(expected, value, epsilon = 1.0e-9));
+        }
+
+        let mut layout = CsvLayout::from_columns("time_ns,x,yaw").unwrap();
+        layout.delimiter = ';';
+        let csv = to_string(&trajectory, &TrajectoryFormat::Csv(layout));
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!("time_ns;x;yaw", lines[0]);
+        assert!(lines[1].starts_with("1250000000;1.5;1.57"));
+        assert!(CsvLayout::from_columns("time,speed").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let buffer = make_buffer();
+        let trajectory = sample_trajectory(&buffer, "map", "base_link", &Stamp::from_nanos(0),
+                                           &Stamp::from_nanos(i64::max_value()), Some(&Stamp::from_nanos(100_000_000))).unwrap();
+        let mut times = Vec::new();
+        write_times(&mut times, &trajectory).unwrap();
+        let times = read_times(times.as_slice()).unwrap();
+        let formats = [TrajectoryFormat::Tum, TrajectoryFormat::Kitti, TrajectoryFormat::Csv(CsvLayout::default()),
+                       TrajectoryFormat::Csv(CsvLayout::from_columns("time_ns,x,y,yaw").unwrap())];
+        for format in formats.iter() {
+            let text = to_string(&trajectory, format);
+            let imported = read_trajectory(text.as_bytes(), format, "map", "ground_truth", Some(&times)).unwrap();
+            assert_eq!(trajectory.len(), imported.len());
+            for (a, b) in trajectory.iter().zip(imported.iter()) {
+                assert_eq!(a.stamp, b.stamp);
+                assert_eq!("ground_truth", b.child_frame_id);
+                assert!((a.translation.vector - b.translation.vector).norm() < 1.0e-9);
+                assert!(a.rotation.angle_to(&b.rotation) < 1.0e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_import_as_frame() {
+        let tum = "# ground truth\n1.0 1 0 0 0 0 0 1\n\n2.000000001 3 0 0 0 0 0 1\n";
+        let trajectory = read_trajectory(tum.as_bytes(), &TrajectoryFormat::Tum, "odom", "ground_truth", None).unwrap();
+        assert_eq!(Stamp::from_nanos(2_000_000_001), trajectory[1].stamp);
+
+        let mut buffer = make_buffer();
+        for transform in trajectory.iter() {
+            buffer.set_transform(transform, false).unwrap();
+        }
+        let res = buffer.lookup_transform("base_link", "ground_truth", &Stamp::from_nanos(1_500_000_000)).unwrap();
+        assert!(abs_diff_eq!(1.0, res.translation.vector.x, epsilon = 1.0e-6));
+
+        assert!(read_trajectory("1.0 1 0 0".as_bytes(), &TrajectoryFormat::Tum, "odom", "gt", None).is_err());
+        assert!(read_trajectory("1 0 0 0 0 1 0 0 0 0 1 0".as_bytes(), &TrajectoryFormat::Kitti, "odom", "gt", None).is_err());
+        let times = read_times("0.5\n".as_bytes()).unwrap();
+        assert_eq!(vec![Stamp::from_nanos(500_000_000)], times);
+        let csv = TrajectoryFormat::Csv(CsvLayout::from_columns("time,x,qx").unwrap());
+        assert!(read_trajectory("time,x,qx\n1.0,2.0,0.0".as_bytes(), &csv, "odom", "gt", None).is_err());
+        assert!(read_trajectory("1.0 1 0 0 0 0 0 0".as_bytes(), &TrajectoryFormat::Tum, "odom", "gt", None).is_err());
+    }
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(Some(1_500_000_000_000_000_000), parse_seconds("1.5e9"));
+        assert_eq!(Some(1_305_031_102_175_304_960), parse_seconds("1.305031102175304890e+09"));
+        assert_eq!(Some(1_000_000_000), parse_seconds("0.9999999999"));
+        assert_eq!(Some(-1_500_000_000), parse_seconds("-1.5"));
+        assert_eq!(None, parse_seconds("1e20"));
+        let times = read_times("1.5e0\n".as_bytes()).unwrap();
+        assert_eq!(vec![Stamp::from_nanos(1_500_000_000)], times);
+    }
+
+}
@@ -1,6 +1,11 @@
 use nalgebra::geometry::{Translation3, Quaternion, UnitQuaternion};
 use nalgebra::Vector3;
-use rosrust::{Time, Duration}; 
+#[cfg(feature = "ros")]
+use rosrust::{Time, Duration};
+#[cfg(not(feature = "ros"))]
+use super::time::{Time, Duration};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 
 
 pub type NVector3 = Vector3<f64>;
@@ -10,11 +15,15 @@ pub type FrameId = u32;
 pub type Stamp = Duration;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TransformStorage {
     pub frame_id       : FrameId,
     pub child_frame_id : FrameId,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::translation_xyz"))]
     pub translation    : NTranslation3,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::rotation_xyzw"))]
     pub rotation       : NQuaternion,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::stamp_nanos"))]
     pub stamp          : Stamp          
 }
 
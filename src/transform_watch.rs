@@ -0,0 +1,282 @@
+use super::transform_storage::Stamp;
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::{Buffer, Transform};
+use super::async_lookup::TransformWaiters;
+
+use futures_core::Stream;
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::task::{Context, Poll, Waker};
+
+// a slow consumer only misses old updates instead of growing without bound
+const MAX_QUEUED_UPDATES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeAlignment {
+    // compose every link at the newest time they all have data for, like a
+    // tf2 lookup at time zero, and stamp the result with that time
+    Latest,
+    // compose every link at the stamp of the sample that triggered the update
+    Updated
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    // updates closer than this to the last yielded one are skipped
+    pub min_period : Option<Stamp>,
+    pub alignment  : TimeAlignment
+}
+
+impl Default for WatchOptions {
+    fn default() -> WatchOptions {
+        WatchOptions {
+            min_period : None,
+            alignment  : TimeAlignment::Latest
+        }
+    }
+}
+
+// Inserts seen by one watch, fed by the buffer's waiters
+pub struct WatchQueue {
+    updates : Mutex<VecDeque<(String, Stamp)>>,
+    waker   : Mutex<Option<Waker>>,
+    ready   : Condvar
+}
+
+impl WatchQueue {
+
+    fn new() -> WatchQueue {
+        WatchQueue {
+            updates : Mutex::new(VecDeque::new()),
+            waker   : Mutex::new(None),
+            ready   : Condvar::new()
+        }
+    }
+
+    pub fn push(&self, frame: &str, stamp: &Stamp) {
+        {
+            let mut updates = self.updates.lock().unwrap();
+            if updates.len() >= MAX_QUEUED_UPDATES {
+                updates.pop_front();
+            }
+            updates.push_back((frame.to_string(), stamp.clone()));
+        }
+        self.ready.notify_all();
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+
+}
+
+pub struct TransformWatch {
+    buffer       : Arc<RwLock<Buffer>>,
+    target_frame : String,
+    source_frame : String,
+    options      : WatchOptions,
+    queue        : Arc<WatchQueue>,
+    // kept so that drop does not need the buffer lock
+    waiters      : Arc<TransformWaiters>,
+    last_stamp   : Option<i64>
+}
+
+impl TransformWatch {
+
+    pub fn new(buffer: Arc<RwLock<Buffer>>, target_frame: &str, source_frame: &str, options: WatchOptions) -> TransformWatch {
+        let queue = Arc::new(WatchQueue::new());
+        let waiters = {
+            let buffer = buffer.read().unwrap();
+            let mut frames = buffer.get_chain_frame_names(target_frame);
+            frames.extend(buffer.get_chain_frame_names(source_frame));
+            let waiters = buffer.get_waiters();
+            waiters.add_watcher(&frames, &queue);
+            waiters
+        };
+        TransformWatch {
+            buffer,
+            target_frame : target_frame.to_string(),
+            source_frame : source_frame.to_string(),
+            options,
+            queue,
+            waiters,
+            last_stamp   : None
+        }
+    }
+
+    // turns one insert into a composed transform, if it concerns the watched chain
+    fn handle_update(&mut self, frame: &str, stamp: &Stamp) -> Option<Result<Transform, TfError>> {
+        let stamp_nanos = stamp.nanos();
+        if let (Some(min_period), Some(last_stamp)) = (&self.options.min_period, self.last_stamp) {
+            if stamp_nanos < last_stamp + min_period.nanos() {
+                return None;
+            }
+        }
+
+        let buffer = self.buffer.read().unwrap();
+        // the watch stays registered for frames that left its chain
+        let in_chain = buffer.get_chain_frame_names(&self.target_frame).iter()
+            .chain(buffer.get_chain_frame_names(&self.source_frame).iter())
+            .any(|x| x == frame);
+        if !in_chain {
+            return None;
+        }
+        let time = match self.options.alignment {
+            TimeAlignment::Latest => match buffer.get_latest_common_time(&self.target_frame, &self.source_frame) {
+                Ok(time) => time,
+                Err(_) => return None
+            },
+            TimeAlignment::Updated => stamp.clone()
+        };
+        match buffer.lookup_transform(&self.target_frame, &self.source_frame, &time) {
+            Ok(mut transform) => {
+                self.last_stamp = Some(stamp_nanos);
+                // a static chain is the same at any time
+                if time.nanos() == 0 {
+                    transform.stamp = stamp.clone();
+                }
+                Some(Ok(transform))
+            },
+            // the other links may not have caught up with this stamp yet
+            Err(TfError::ExtrapolationError1) | Err(TfError::ExtrapolationError2) | Err(TfError::ExtrapolationError3) |
+            Err(TfError::ExtrapolationError(_)) => None,
+            Err(TfError::LookupError(_)) | Err(TfError::ConnectivityError(_)) => None,
+            Err(err) => Some(Err(err))
+        }
+    }
+
+    // the next transform if an update is already queued, without blocking
+    pub fn try_next(&mut self) -> Option<Result<Transform, TfError>> {
+        loop {
+            let update = self.queue.updates.lock().unwrap().pop_front();
+            let (frame, stamp) = update?;
+            if let Some(res) = self.handle_update(&frame, &stamp) {
+                return Some(res);
+            }
+        }
+    }
+
+}
+
+// Blocks until the chain changes, never ends
+impl Iterator for TransformWatch {
+    type Item = Result<Transform, TfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (frame, stamp) = {
+                let mut updates = self.queue.updates.lock().unwrap();
+                while updates.is_empty() {
+                    updates = self.queue.ready.wait(updates).unwrap();
+                }
+                updates.pop_front().unwrap()
+            };
+            if let Some(res) = self.handle_update(&frame, &stamp) {
+                return Some(res);
+            }
+        }
+    }
+}
+
+impl Stream for TransformWatch {
+    type Item = Result<Transform, TfError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        // registered first, so that a push racing with the check still wakes us
+        *self.queue.waker.lock().unwrap() = Some(cx.waker().clone());
+        match self.try_next() {
+            Some(res) => Poll::Ready(Some(res)),
+            None => Poll::Pending
+        }
+    }
+}
+
+impl Drop for TransformWatch {
+    fn drop(&mut self) {
+        self.waiters.remove_watcher(&self.queue);
+    }
+}
+
+pub fn watch(buffer: &Arc<RwLock<Buffer>>, target_frame: &str, source_frame: &str, options: WatchOptions) -> TransformWatch {
+    TransformWatch::new(buffer.clone(), target_frame, source_frame, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{NTranslation3, NQuaternion};
+
+    fn make_transform(parent: &str, child: &str, x: f64, nanos: i64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(nanos)
+        }
+    }
+
+    #[test]
+    fn test_watch_chain_updates() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        buffer.write().unwrap().set_transform(&make_transform("map", "odom", 1.0, 0), false).unwrap();
+        let mut watch = watch(&buffer, "map", "base_link", WatchOptions::default());
+
+        buffer.write().unwrap().set_transform(&make_transform("odom", "base_link", 2.0, 0), false).unwrap();
+        // not part of the chain
+        buffer.write().unwrap().set_transform(&make_transform("base_link", "laser", 2.0, 0), false).unwrap();
+        assert_eq!(1, watch.queue.updates.lock().unwrap().len());
+
+        let res = watch.next().unwrap().unwrap();
+        assert!(abs_diff_eq!(3.0, res.translation.vector.x));
+        assert!(watch.try_next().is_none());
+    }
+
+    #[test]
+    fn test_watch_latest_common_time() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let mut watch = watch(&buffer, "map", "base_link", WatchOptions::default());
+        buffer.write().unwrap().set_transform(&make_transform("odom", "base_link", 1.0, 150), false).unwrap();
+        // joins the chain through the new parent of base_link
+        buffer.write().unwrap().set_transform(&make_transform("map", "odom", 0.0, 100), false).unwrap();
+        buffer.write().unwrap().set_transform(&make_transform("map", "odom", 2.0, 200), false).unwrap();
+
+        let res = watch.try_next().unwrap().unwrap();
+        assert_eq!(Stamp::from_nanos(150), res.stamp);
+        assert!(abs_diff_eq!(2.0, res.translation.vector.x));
+    }
+
+    #[test]
+    fn test_watch_rate_limit_and_alignment() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let options = WatchOptions {
+            min_period : Some(Stamp::from_nanos(100)),
+            alignment  : TimeAlignment::Updated
+        };
+        let mut watch = watch(&buffer, "map", "odom", options);
+        for i in 0..5 {
+            buffer.write().unwrap().set_transform(&make_transform("map", "odom", i as f64, i * 50), false).unwrap();
+        }
+        let stamps: Vec<i64> = std::iter::from_fn(|| watch.try_next())
+            .map(|x| x.unwrap().stamp.nanos())
+            .collect();
+        // updates 50 and 150 come too soon after the previous yield
+        assert_eq!(vec![0, 100, 200], stamps);
+    }
+
+    #[test]
+    fn test_watch_stream() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let mut watch = watch(&buffer, "map", "odom", WatchOptions::default());
+        let writer = buffer.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            writer.write().unwrap().set_transform(&make_transform("map", "odom", 4.0, 10), false).unwrap();
+        });
+        let res = futures::executor::block_on(futures::StreamExt::next(&mut watch)).unwrap().unwrap();
+        handle.join().unwrap();
+        assert!(abs_diff_eq!(4.0, res.translation.vector.x));
+    }
+
+}
@@ -0,0 +1,281 @@
+use super::time_cache_interface::TfError;
+use super::tf_buffer::tf::Transform;
+#[cfg(feature = "ros")]
+use super::msg_conversion::{transform_from_msg, transform_to_msg};
+#[cfg(feature = "ros")]
+use super::msg::tf2_msgs::TFMessage;
+
+use std::sync::{Arc, Mutex, Weak};
+
+// called with the batch and, when the transport knows it, the name of the
+// node that published it
+pub type TransformCallback = Box<dyn Fn(&[Transform], Option<&str>) + Send + Sync>;
+
+// Keeps a subscription alive, dropping it unsubscribes
+pub struct Subscription {
+    _handle : Box<dyn Send>
+}
+
+impl Subscription {
+
+    pub fn new<H: Send + 'static>(handle: H) -> Subscription {
+        Subscription {
+            _handle : Box::new(handle)
+        }
+    }
+
+}
+
+// Moves batches of transforms between broadcasters and listeners. Static
+// batches are latched: late subscribers get the last one that was published.
+pub trait TransformTransport: Send + Sync {
+    fn publish(&self, transforms: &[Transform], is_static: bool) -> Result<(), TfError>;
+
+    fn subscribe(&self, is_static: bool, callback: TransformCallback) -> Result<Subscription, TfError>;
+}
+
+#[cfg(feature = "ros")]
+fn ros_error(err: rosrust::error::Error) -> TfError {
+    TfError::Ros(err.to_string())
+}
+
+#[cfg(feature = "ros")]
+fn topic(is_static: bool) -> &'static str {
+    if is_static { "/tf_static" } else { "/tf" }
+}
+
+// /tf and /tf_static through rosrust. Broadcasters advertise their topic
+// up front, as the first message would otherwise go out before any ROS1
+// subscriber is connected. Listeners never advertise.
+#[cfg(feature = "ros")]
+pub struct RosTransformTransport {
+    tf_publisher        : Mutex<Option<rosrust::Publisher<TFMessage>>>,
+    tf_static_publisher : Mutex<Option<rosrust::Publisher<TFMessage>>>
+}
+
+#[cfg(feature = "ros")]
+impl RosTransformTransport {
+
+    pub fn new() -> RosTransformTransport {
+        RosTransformTransport {
+            tf_publisher        : Mutex::new(None),
+            tf_static_publisher : Mutex::new(None)
+        }
+    }
+
+    pub fn advertise(&self, is_static: bool) -> Result<(), TfError> {
+        self.advertised(is_static).map(|_| ())
+    }
+
+    fn advertised(&self, is_static: bool) -> Result<std::sync::MutexGuard<Option<rosrust::Publisher<TFMessage>>>, TfError> {
+        let mut publisher = if is_static {
+            self.tf_static_publisher.lock().unwrap()
+        } else {
+            self.tf_publisher.lock().unwrap()
+        };
+        if publisher.is_none() {
+            let mut advertised = rosrust::publish(topic(is_static)).map_err(ros_error)?;
+            advertised.set_latching(is_static);
+            *publisher = Some(advertised);
+        }
+        Ok(publisher)
+    }
+
+}
+
+#[cfg(feature = "ros")]
+impl TransformTransport for RosTransformTransport {
+    fn publish(&self, transforms: &[Transform], is_static: bool) -> Result<(), TfError> {
+        let mut publisher = self.advertised(is_static)?;
+        let msg = TFMessage {
+            transforms : transforms.iter().map(transform_to_msg).collect()
+        };
+        publisher.as_mut().unwrap().send(msg).map_err(ros_error)
+    }
+
+    fn subscribe(&self, is_static: bool, callback: TransformCallback) -> Result<Subscription, TfError> {
+        let subscriber = rosrust::subscribe_with_ids(topic(is_static), move |msg: TFMessage, caller_id: &str| {
+            let transforms: Vec<Transform> = msg.transforms.iter().map(transform_from_msg).collect();
+            callback(&transforms, Some(caller_id));
+        }).map_err(|err| TfError::Ros(format!("could not subscribe to {}: {}", topic(is_static), err)))?;
+        Ok(Subscription::new(subscriber))
+    }
+}
+
+struct ChannelSubscriber {
+    id        : usize,
+    is_static : bool,
+    callback  : Arc<TransformCallback>
+}
+
+#[derive(Default)]
+struct ChannelState {
+    next_id     : usize,
+    subscribers : Vec<ChannelSubscriber>,
+    latched     : Option<Vec<Transform>>
+}
+
+struct ChannelSubscription {
+    state : Weak<Mutex<ChannelState>>,
+    id    : usize
+}
+
+impl Drop for ChannelSubscription {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.upgrade() {
+            state.lock().unwrap().subscribers.retain(|x| x.id != self.id);
+        }
+    }
+}
+
+// In-memory transport for programs without a ROS master and for tests.
+// Clones share the same topics; callbacks run on the publishing thread.
+#[derive(Clone, Default)]
+pub struct ChannelTransport {
+    state : Arc<Mutex<ChannelState>>
+}
+
+impl ChannelTransport {
+
+    pub fn new() -> ChannelTransport {
+        ChannelTransport::default()
+    }
+
+}
+
+impl TransformTransport for ChannelTransport {
+    fn publish(&self, transforms: &[Transform], is_static: bool) -> Result<(), TfError> {
+        let callbacks: Vec<Arc<TransformCallback>> = {
+            let mut state = self.state.lock().unwrap();
+            if is_static {
+                state.latched = Some(transforms.to_vec());
+            }
+            state.subscribers.iter()
+                .filter(|x| x.is_static == is_static)
+                .map(|x| x.callback.clone())
+                .collect()
+        };
+        // called unlocked, so that callbacks may publish or subscribe
+        for callback in callbacks {
+            callback(transforms, None);
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self, is_static: bool, callback: TransformCallback) -> Result<Subscription, TfError> {
+        let callback = Arc::new(callback);
+        let (id, latched) = {
+            let mut state = self.state.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.subscribers.push(ChannelSubscriber { id, is_static, callback: callback.clone() });
+            (id, if is_static { state.latched.clone() } else { None })
+        };
+        if let Some(transforms) = latched {
+            callback(&transforms, None);
+        }
+        Ok(Subscription::new(ChannelSubscription {
+            state : Arc::downgrade(&self.state),
+            id
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transform_storage::{Stamp, NTranslation3, NQuaternion};
+    use super::super::tf_buffer::tf::Buffer;
+    use super::super::listener::TransformListener;
+    use super::super::broadcaster::{TransformBroadcaster, StaticTransformBroadcaster};
+    use super::super::frame_remapping::FrameRemapping;
+    use std::sync::RwLock;
+
+    fn make_transform(parent: &str, child: &str, x: f64) -> Transform {
+        Transform {
+            frame_id       : parent.to_string(),
+            child_frame_id : child.to_string(),
+            translation    : NTranslation3::new(x, 0.0, 0.0),
+            rotation       : NQuaternion::identity(),
+            stamp          : Stamp::from_nanos(0)
+        }
+    }
+
+    #[test]
+    fn test_channel_latches_static() {
+        let transport = ChannelTransport::new();
+        transport.publish(&[make_transform("map", "odom", 1.0)], true).unwrap();
+        transport.publish(&[make_transform("map", "odom", 2.0)], false).unwrap();
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let subscription = transport.subscribe(true, Box::new(move |transforms: &[Transform], _| {
+            sink.lock().unwrap().extend_from_slice(transforms);
+        })).unwrap();
+        assert_eq!(1, received.lock().unwrap().len());
+
+        drop(subscription);
+        transport.publish(&[make_transform("map", "odom", 3.0)], true).unwrap();
+        assert_eq!(1, received.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_listener_over_channel() {
+        let transport = Arc::new(ChannelTransport::new());
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let listener = TransformListener::with_transport(buffer, transport.as_ref()).unwrap();
+
+        let mut broadcaster = TransformBroadcaster::with_transport(transport.clone());
+        let mut static_broadcaster = StaticTransformBroadcaster::with_transport(transport.clone());
+        static_broadcaster.send_transforms(&[make_transform("map", "odom", 1.0)]).unwrap();
+        broadcaster.send_transforms(&[make_transform("odom", "base_link", 2.0)]).unwrap();
+
+        let res = listener.lookup_transform("map", "base_link", &Stamp::from_nanos(0)).unwrap();
+        assert!(abs_diff_eq!(3.0, res.translation.vector.x));
+    }
+
+    #[test]
+    fn test_listeners_with_remapping() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let robots: Vec<ChannelTransport> = (0..2).map(|_| ChannelTransport::new()).collect();
+        let _listeners: Vec<TransformListener> = robots.iter().enumerate().map(|(i, transport)| {
+            let remapping = FrameRemapping::new().with_prefix(&format!("robot{}", i)).unwrap();
+            TransformListener::with_remapping(buffer.clone(), transport, remapping).unwrap()
+        }).collect();
+        for (i, transport) in robots.iter().enumerate() {
+            transport.publish(&[make_transform("odom", "base_link", i as f64)], false).unwrap();
+        }
+
+        let buffer = buffer.read().unwrap();
+        let res = buffer.lookup_transform("robot1/odom", "robot1/base_link", &Stamp::from_nanos(0)).unwrap();
+        assert!(abs_diff_eq!(1.0, res.translation.vector.x));
+        assert!(buffer.frame_exists("robot0/base_link"));
+    }
+
+    // delivers one batch on subscribe, as if published by `caller_id`
+    struct NamedTransport {
+        caller_id : &'static str
+    }
+
+    impl TransformTransport for NamedTransport {
+        fn publish(&self, _transforms: &[Transform], _is_static: bool) -> Result<(), TfError> {
+            Ok(())
+        }
+
+        fn subscribe(&self, is_static: bool, callback: TransformCallback) -> Result<Subscription, TfError> {
+            if !is_static {
+                callback(&[make_transform("odom", "base_link", 1.0)], Some(self.caller_id));
+            }
+            Ok(Subscription::new(()))
+        }
+    }
+
+    #[test]
+    fn test_listener_keeps_caller_id() {
+        let buffer = Arc::new(RwLock::new(Buffer::new()));
+        let _listener = TransformListener::with_transport(buffer.clone(), &NamedTransport { caller_id: "/odometry" }).unwrap();
+        let statistics = buffer.read().unwrap().get_frame_statistics();
+        assert_eq!("/odometry", statistics[0].authority);
+    }
+
+}
@@ -0,0 +1,70 @@
+// Checks that the ring buffer cache does not allocate once it is built. The
+// counting allocator replaces the global one for the whole test binary,
+// which is why this test lives on its own.
+
+use rosrust_tf::ring_buffer_cache::RingBufferCache;
+use rosrust_tf::time_cache_interface::TimeCacheInterface;
+use rosrust_tf::transform_storage::{TransformStorage, Stamp, NTranslation3, NQuaternion};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+// counts the allocations of the current thread only, so that tests
+// running in parallel do not interfere
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|x| x.set(x.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|x| x.get())
+}
+
+fn make_transform_storage(parent: u32, x: f64, nanos: i64) -> TransformStorage {
+    TransformStorage {
+        frame_id       : parent,
+        child_frame_id : 2u32,
+        translation    : NTranslation3::new(x, 0.0, 0.0),
+        rotation       : NQuaternion::identity(),
+        stamp          : Stamp::from_nanos(nanos)
+    }
+}
+
+#[test]
+fn no_allocation_on_insert_and_lookup() {
+    let mut cache = RingBufferCache::new(64);
+    let reader = cache.reader();
+
+    let before = allocations();
+    for i in 1..1000 {
+        cache.insert_data(make_transform_storage(1, i as f64, i * 100));
+        let ts = cache.get_data(&Stamp::from_nanos(i * 100 - 50)).unwrap_or_else(|_| make_transform_storage(0, 0.0, 0));
+        let latest = reader.get_data(&Stamp::from_nanos(0)).unwrap();
+        assert!(ts.translation.vector.x <= latest.translation.vector.x);
+    }
+    assert_eq!(0, allocations() - before);
+}